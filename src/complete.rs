@@ -0,0 +1,193 @@
+// Tab Completion
+//
+// With a Completer set, Tab (Action::Complete) asks it for the candidates
+// at the cursor, each with the byte range of the line it replaces.  A single
+// candidate is inserted straight away, several are shown in a popup above
+// the cursor (below it when there is no room above).  Up / Down, Ctrl-P /
+// Ctrl-N or Shift-Tab move through the popup, Enter or Tab accept the
+// selected candidate and Esc or Ctrl-G close it.  Any other key closes the
+// popup and is then handled as usual.
+//
+// Without a Completer, with a selection, or with nothing to complete, Tab
+// inserts a tab as before.
+
+use crate::{CmdKind, Editor, Result, display_width, idx_at_col};
+use crossterm::{
+    QueueableCommand, cursor,
+    event::{KeyCode, KeyModifiers},
+    style::{Attribute, Color, ContentStyle, PrintStyledContent, StyledContent},
+};
+use std::ops::Range;
+
+const POPUP_ROWS: usize = 8;
+
+/// A completion: replace range of the line with replacement
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    /// Byte range of the line to replace
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+/// Source of completions for Tab
+pub trait Completer {
+    /// The candidates for line, with the cursor at byte index pos
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Candidate>;
+}
+
+impl<F: FnMut(&str, usize) -> Vec<Candidate>> Completer for F {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Candidate> {
+        self(line, pos)
+    }
+}
+
+// Candidates shown in the popup
+pub(crate) struct Completion {
+    candidates: Vec<Candidate>,
+    selected: usize,
+}
+
+impl Editor {
+    // Tab: Complete at the cursor, returning false when there is nothing to
+    // complete
+    pub(crate) fn complete(&mut self) -> Result<bool> {
+        let Some(completer) = &mut self.completer else {
+            return Ok(false);
+        };
+        if self.anchor.is_some() {
+            return Ok(false);
+        }
+        self.lidx = self.lidx.min(self.lines[self.lineidx].len());
+        let line = &self.lines[self.lineidx];
+        let mut candidates: Vec<Candidate> = completer
+            .complete(line, self.lidx)
+            .into_iter()
+            .filter(|c| c.range.start <= c.range.end && line.get(c.range.clone()).is_some())
+            .collect();
+        match candidates.len() {
+            0 => return Ok(false),
+            1 => self.complete_with(candidates.remove(0))?,
+            _ => {
+                self.completion = Some(Completion {
+                    candidates,
+                    selected: 0,
+                });
+                self.redraw()?;
+            }
+        }
+        Ok(true)
+    }
+
+    // Replace the candidate's range with its replacement
+    fn complete_with(&mut self, candidate: Candidate) -> Result<()> {
+        let lineidx = self.lineidx;
+        self.save_undo(CmdKind::Edit);
+        self.delete_range(
+            (lineidx, candidate.range.start),
+            (lineidx, candidate.range.end),
+        )?;
+        self.insert_text(&candidate.replacement)?;
+        self.redraw()
+    }
+
+    /// Handle a key while the completion popup is open, returns false if the
+    /// key closed the popup and still needs to be handled
+    pub(crate) fn completion_key(
+        &mut self,
+        code: KeyCode,
+        modifiers: KeyModifiers,
+    ) -> Result<bool> {
+        let Some(completion) = &mut self.completion else {
+            return Ok(false);
+        };
+        let num = completion.candidates.len();
+        match (code, modifiers) {
+            (KeyCode::Up | KeyCode::BackTab, _) | (KeyCode::Char('p'), KeyModifiers::CONTROL) => {
+                completion.selected = (completion.selected + num - 1) % num;
+                self.redraw()?;
+            }
+            (KeyCode::Down, _) | (KeyCode::Char('n'), KeyModifiers::CONTROL) => {
+                completion.selected = (completion.selected + 1) % num;
+                self.redraw()?;
+            }
+            (KeyCode::Enter | KeyCode::Tab, _) => {
+                let candidate = completion.candidates.swap_remove(completion.selected);
+                self.completion = None;
+                self.complete_with(candidate)?;
+            }
+            (KeyCode::Esc, _) | (KeyCode::Char('g'), KeyModifiers::CONTROL) => {
+                self.completion = None;
+                self.redraw()?;
+            }
+            _ => {
+                self.completion = None;
+                self.redraw()?;
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Draw the completion popup over the edit area, next to the cursor
+    pub(crate) fn draw_completion(&mut self) -> Result<()> {
+        let Some(completion) = &self.completion else {
+            return Ok(());
+        };
+        let top = self.printlines + 2;
+        let above = self.cury.saturating_sub(top) as usize;
+        let below = self.sizey.saturating_sub(self.cury + 1) as usize;
+        let wanted = completion.candidates.len().min(POPUP_ROWS);
+        let (rows, y) = if above >= wanted || above >= below {
+            let rows = wanted.min(above);
+            (rows, self.cury - rows as u16)
+        } else {
+            (wanted.min(below), self.cury + 1)
+        };
+        if rows == 0 {
+            return Ok(());
+        }
+
+        // Candidates as one line each, cut to the screen width
+        let ts = self.tabstop as usize;
+        let maxwidth = (self.sizex as usize).saturating_sub(2);
+        let labels: Vec<String> = completion
+            .candidates
+            .iter()
+            .map(|c| {
+                let label = c
+                    .replacement
+                    .lines()
+                    .next()
+                    .unwrap_or("")
+                    .replace('\t', " ");
+                label[..idx_at_col(&label, maxwidth, ts)].to_string()
+            })
+            .collect();
+        let width = labels
+            .iter()
+            .map(|label| display_width(label, ts))
+            .max()
+            .unwrap_or(0);
+        let x = self.curx.min(self.sizex.saturating_sub(width as u16 + 2));
+
+        let mut style = ContentStyle::new();
+        style.background_color = Some(Color::DarkGrey);
+        let mut selected = style;
+        selected.attributes.set(Attribute::Reverse);
+        let first = (completion.selected + 1).saturating_sub(rows);
+        for (row, n) in (first..first + rows).enumerate() {
+            let label = &labels[n];
+            let pad = " ".repeat(width - display_width(label, ts));
+            let text = format!(" {label}{pad} ");
+            let style = if n == completion.selected {
+                selected
+            } else {
+                style
+            };
+            self.term.queue(cursor::MoveTo(x, y + row as u16))?;
+            self.term
+                .queue(PrintStyledContent(StyledContent::new(style, text)))?;
+        }
+        Ok(())
+    }
+}
@@ -5,6 +5,8 @@ pub type Result<T> = core::result::Result<T, Error>;
 #[derive(Debug, From)]
 pub enum Error {
     Msg(&'static str),
+    /// The [`AsyncEditor`] behind an [`EditorHandle`] has been dropped
+    EditorClosed,
     /// [`SharedStdout`] has already dropped
     RedrawRcError,
     SharedStdoutClosed,
@@ -12,6 +14,8 @@ pub enum Error {
     Fmt(std::fmt::Error),
     #[from]
     Io(std::io::Error),
+    #[from]
+    Regex(regex::Error),
     //#[from]
     //SerdeJson(serde_json::Error)
 }
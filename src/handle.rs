@@ -0,0 +1,123 @@
+// Editor Handle
+//
+// An EditorHandle controls the edit area from other tasks, the way
+// SharedStdout prints to it: commands are sent down a channel, and
+// async_editor() carries them out between key presses.  Commands that
+// answer (the text, the cursor) send the answer back on a channel of their
+// own, made for the one request.
+//
+// Commands that change the text end any search, find and replace or
+// completion in progress, and are one undo step each.
+
+use crate::{CmdKind, Editor, Error, Result};
+use thingbuf::mpsc::{Sender, channel};
+
+// What an EditorHandle asks of the editor
+#[derive(Clone, Default)]
+pub(crate) enum Command {
+    #[default]
+    None,
+    Clear,
+    Cursor(Sender<(usize, usize)>),
+    Insert(String),
+    MoveCursor(usize, usize),
+    SetText(String),
+    Text(Sender<String>),
+}
+
+/// Send + Clone handle to read and change the edit area from other tasks
+///
+/// Cursor positions are (line, byte index in the line), counted from 0.
+#[derive(Clone)]
+pub struct EditorHandle {
+    pub(crate) command_tx: Sender<Command>,
+}
+
+impl EditorHandle {
+    /// Clear the edit area
+    pub async fn clear(&self) -> Result<()> {
+        self.send(Command::Clear).await
+    }
+
+    /// Where the cursor is, as (line, byte index)
+    pub async fn cursor(&self) -> Result<(usize, usize)> {
+        let (tx, rx) = channel(1);
+        self.send(Command::Cursor(tx)).await?;
+        rx.recv().await.ok_or(Error::EditorClosed)
+    }
+
+    /// Insert text at the cursor, over the selection if there is one
+    pub async fn insert_at_cursor(&self, text: &str) -> Result<()> {
+        self.send(Command::Insert(text.to_string())).await
+    }
+
+    /// Move the cursor to (line, byte index), kept inside the text
+    pub async fn move_cursor(&self, line: usize, idx: usize) -> Result<()> {
+        self.send(Command::MoveCursor(line, idx)).await
+    }
+
+    async fn send(&self, command: Command) -> Result<()> {
+        self.command_tx
+            .send(command)
+            .await
+            .map_err(|_| Error::EditorClosed)
+    }
+
+    /// Replace the text, with the cursor at the end
+    pub async fn set_text(&self, text: &str) -> Result<()> {
+        self.send(Command::SetText(text.to_string())).await
+    }
+
+    /// The text in the edit area
+    pub async fn text(&self) -> Result<String> {
+        let (tx, rx) = channel(1);
+        self.send(Command::Text(tx)).await?;
+        rx.recv().await.ok_or(Error::EditorClosed)
+    }
+}
+
+impl Editor {
+    // Carry out a command from an EditorHandle
+    pub(crate) fn run_command(&mut self, command: Command) -> Result<()> {
+        if matches!(
+            command,
+            Command::Clear | Command::Insert(_) | Command::MoveCursor(..) | Command::SetText(_)
+        ) {
+            self.search = None;
+            self.replace = None;
+            self.completion = None;
+            self.invalid = None;
+            self.last_cmd = std::mem::replace(&mut self.this_cmd, CmdKind::Other);
+        }
+        match command {
+            Command::None => {}
+            Command::Clear => {
+                self.save_undo(CmdKind::Edit);
+                self.set_text("", (0, 0))?;
+            }
+            Command::Cursor(tx) => {
+                let _ = tx.try_send((self.lineidx, self.lidx));
+            }
+            Command::Insert(text) => {
+                let text = text.replace("\r\n", "\n").replace('\r', "\n");
+                self.paste(&text)?;
+            }
+            Command::MoveCursor(line, idx) => {
+                self.anchor = None;
+                (self.lineidx, self.lidx) = self.clamp_pos((line, idx));
+                self.setrow();
+                self.setpos()?;
+                self.redraw()?;
+            }
+            Command::SetText(text) => {
+                let text = text.replace("\r\n", "\n").replace('\r', "\n");
+                self.save_undo(CmdKind::Edit);
+                self.set_text(&text, (usize::MAX, usize::MAX))?;
+            }
+            Command::Text(tx) => {
+                let _ = tx.try_send(self.text());
+            }
+        }
+        self.update_hint()
+    }
+}
@@ -0,0 +1,151 @@
+// Syntax Highlighting
+//
+// With a Highlighter set, every line of the edit area is drawn with the
+// styled byte ranges it returns, under the selection and search highlights.
+// Tabs, the > overflow marker and horizontal scrolling work as before,
+// since the styles are applied per grapheme by drawline.
+//
+// Highlighting may carry state from line to line, for fenced code blocks
+// and the like: the highlighter gets the state at the end of the line above
+// (0 for the first line) and returns the state at the end of this one.
+//
+// The styles and states of the lines drawn so far are kept, and only the
+// lines the rope reports changed are highlighted again.  Past those, a line
+// starting in the same state as before is highlighted as before, and so is
+// every line after it, so the work stops there.  When an edit changes the
+// state at the end of the current line, the lines below change too, and
+// redrawline redraws the whole edit area instead of the one line.
+
+use crate::Editor;
+use crossterm::style::{Attribute, Color, ContentStyle};
+use std::ops::Range;
+
+/// Styles for the text in the edit area
+pub trait Highlighter {
+    /// Styled byte ranges of line, given the state at the end of the line
+    /// above (0 for the first line), and the state at the end of this line
+    fn highlight(&mut self, line: &str, state: usize)
+    -> (Vec<(Range<usize>, ContentStyle)>, usize);
+}
+
+impl<F: FnMut(&str, usize) -> (Vec<(Range<usize>, ContentStyle)>, usize)> Highlighter for F {
+    fn highlight(
+        &mut self,
+        line: &str,
+        state: usize,
+    ) -> (Vec<(Range<usize>, ContentStyle)>, usize) {
+        self(line, state)
+    }
+}
+
+/// Highlight Markdown headings, `code` and fenced code blocks
+pub struct MarkdownHighlighter;
+
+impl Highlighter for MarkdownHighlighter {
+    fn highlight(
+        &mut self,
+        line: &str,
+        state: usize,
+    ) -> (Vec<(Range<usize>, ContentStyle)>, usize) {
+        let color = |color| {
+            let mut style = ContentStyle::new();
+            style.foreground_color = Some(color);
+            style
+        };
+        let trimmed = line.trim_start();
+
+        // State 1: Inside a fenced code block
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            let state = if state == 0 { 1 } else { 0 };
+            return (vec![(0..line.len(), color(Color::DarkGrey))], state);
+        }
+        if state != 0 {
+            return (vec![(0..line.len(), color(Color::Green))], state);
+        }
+        if trimmed.starts_with('#') {
+            let mut style = color(Color::Cyan);
+            style.attributes.set(Attribute::Bold);
+            return (vec![(0..line.len(), style)], 0);
+        }
+        let mut spans = Vec::new();
+        let mut open = None;
+        for (i, _) in line.match_indices('`') {
+            match open.take() {
+                Some(start) => spans.push((start..i + 1, color(Color::Green))),
+                None => open = Some(i),
+            }
+        }
+        (spans, 0)
+    }
+}
+
+// A highlighted line
+struct Line {
+    state: usize, // At the start of the line
+    styles: Vec<(Range<usize>, ContentStyle)>,
+    end: usize, // State at the end of the line
+}
+
+// The highlighting of the first lines of the text
+#[derive(Default)]
+pub(crate) struct Highlights {
+    lines: Vec<Line>,
+    len: usize, // Lines in the text when last brought up to date
+}
+
+impl Editor {
+    // Bring the highlighting of lines up to end up to date with the edits
+    // since, returning the lines highlighted again
+    pub(crate) fn highlight_update(&mut self, end: usize) -> Range<usize> {
+        let changed = self.lines.take_changed();
+        let Some(highlighter) = &mut self.highlighter else {
+            self.highlights = Highlights::default();
+            return 0..0;
+        };
+        let highlights = &mut self.highlights;
+        let len = self.lines.len();
+        let drawn = highlights.lines.len();
+        let Range {
+            start,
+            end: changed_end,
+        } = changed.unwrap_or(drawn..drawn);
+        let start = start.min(drawn);
+
+        // The lines from start on, as they were highlighted before.  Lines
+        // from changed_end on were old line idx + highlights.len - len
+        let mut old = highlights.lines.split_off(start);
+        let old_idx = |idx: usize| (idx + highlights.len).checked_sub(len + start);
+        let mut state = highlights.lines.last().map_or(0, |line| line.end);
+        let mut idx = start;
+        while idx < len {
+            if idx >= changed_end
+                && let Some(i) = old_idx(idx)
+                && old.get(i).is_some_and(|line| line.state == state)
+            {
+                highlights.lines.extend(old.drain(i..));
+                break;
+            }
+            if idx >= end {
+                break;
+            }
+            let (styles, next) = highlighter.highlight(&self.lines[idx], state);
+            highlights.lines.push(Line {
+                state,
+                styles,
+                end: next,
+            });
+            state = next;
+            idx += 1;
+        }
+        highlights.len = len;
+        start..idx
+    }
+
+    // Highlighter styles for a line
+    pub(crate) fn highlight_styles(&self, lineidx: usize) -> Vec<(Range<usize>, ContentStyle)> {
+        self.highlights
+            .lines
+            .get(lineidx)
+            .map_or(Vec::new(), |line| line.styles.clone())
+    }
+}
@@ -0,0 +1,113 @@
+// Inline Hints
+//
+// With a Hinter set, the editor asks it for a hint whenever the cursor is at
+// the end of a line, and shows the hint dimmed after the cursor, like the
+// fish shell.  Right or End (Ctrl-E) at the end of the line accept the hint,
+// inserting it; any other key leaves it to be asked for again.
+//
+// The hint is only drawn: it is not part of the lines, so the cursor,
+// setpos and lofs never see it.  It is cut to the columns left of the row,
+// and hidden while selecting, searching, replacing, completing or in vi
+// normal mode.  HistoryHinter hints the rest of the newest input history
+// entry starting with the line.
+
+use crate::{CmdKind, Editor, Result, idx_at_col, vi::Mode};
+use crossterm::{
+    QueueableCommand, cursor,
+    style::{Attribute, Color, ContentStyle, PrintStyledContent, StyledContent},
+};
+
+/// Source of inline hints
+pub trait Hinter {
+    /// Text to show after line, with the cursor at its end.  history is the
+    /// input history, oldest first
+    fn hint(&mut self, line: &str, history: &[String]) -> Option<String>;
+}
+
+impl<F: FnMut(&str, &[String]) -> Option<String>> Hinter for F {
+    fn hint(&mut self, line: &str, history: &[String]) -> Option<String> {
+        self(line, history)
+    }
+}
+
+/// Hint the rest of the newest input history line starting with the line
+pub struct HistoryHinter;
+
+impl Hinter for HistoryHinter {
+    fn hint(&mut self, line: &str, history: &[String]) -> Option<String> {
+        if line.is_empty() {
+            return None;
+        }
+        history
+            .iter()
+            .rev()
+            .flat_map(|entry| entry.lines())
+            .find(|entry| entry.len() > line.len() && entry.starts_with(line))
+            .map(|entry| entry[line.len()..].to_string())
+    }
+}
+
+impl Editor {
+    // Right / End: Insert the hint, returning false when there is none
+    pub(crate) fn accept_hint(&mut self) -> Result<bool> {
+        if self.anchor.is_some() || self.lidx < self.len() {
+            return Ok(false);
+        }
+        let Some(hint) = self.hint.take() else {
+            return Ok(false);
+        };
+        self.save_undo(CmdKind::Edit);
+        self.insert_text(&hint)?;
+        self.redrawline()?;
+        Ok(true)
+    }
+
+    // Draw the hint after the cursor, leaving the cursor where it was
+    pub(crate) fn draw_hint(&mut self) -> Result<()> {
+        let Some(hint) = &self.hint else {
+            return Ok(());
+        };
+        if self.lidx < self.lines[self.lineidx].len() {
+            return Ok(());
+        }
+        let room = (self.gutter() as usize + self.maxwidth()).saturating_sub(self.curx as usize);
+        let hint = hint.replace('\t', " ");
+        let hint = &hint[..idx_at_col(&hint, room, self.tabstop as usize)];
+        let mut style = ContentStyle::new();
+        style.foreground_color = Some(Color::DarkGrey);
+        style.attributes.set(Attribute::Dim);
+        self.term.queue(cursor::MoveTo(self.curx, self.cury))?;
+        self.term
+            .queue(PrintStyledContent(StyledContent::new(style, hint)))?;
+        self.term.queue(cursor::MoveTo(self.curx, self.cury))?;
+        Ok(())
+    }
+
+    // Ask the hinter again after a key, redrawing the line if the hint changed
+    pub(crate) fn update_hint(&mut self) -> Result<()> {
+        let normal = self.vi.as_ref().is_some_and(|vi| vi.mode != Mode::Insert);
+        let line = &self.lines[self.lineidx];
+        let hint = match &mut self.hinter {
+            Some(hinter)
+                if self.lidx >= line.len()
+                    && self.anchor.is_none()
+                    && self.search.is_none()
+                    && self.replace.is_none()
+                    && self.completion.is_none()
+                    && !normal =>
+            {
+                hinter.hint(line, self.history.entries())
+            }
+            _ => None,
+        };
+        // Only the first line of a hint, and nothing for an empty one
+        let hint = hint
+            .and_then(|hint| hint.lines().next().map(str::to_string))
+            .filter(|hint| !hint.is_empty());
+        if hint != self.hint {
+            self.hint = hint;
+            self.redrawline()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,430 @@
+// Input History
+//
+// Submitted text is added to the input history, and recalled with Up on the
+// first line / Down on the last line, or with Ctrl-P / Ctrl-N.  Older
+// entries come up with the cursor on their first line, newer ones with the
+// cursor on their last line, so holding Up or Down steps through whole
+// entries.
+//
+// As in readline, a recalled entry can be edited freely: the edits are kept
+// while moving through the history, but the history itself only changes
+// when the text is submitted, adding it as the newest entry.  The text being
+// written before moving into the history is kept as the draft, one past the
+// newest entry.
+//
+// Submitting text that is already in the history moves it to the newest
+// entry, and past the size limit the oldest entries are dropped.  With a
+// history file, the history is loaded from it, one entry per line with \ and
+// line breaks escaped as \\ and \n, and each submit appends its entry.
+// Loading keeps the newest of any duplicates, so the file is only written
+// out whole, to a temporary file then renamed over the old one, when it
+// grows to twice the size limit or the file or limit change.
+//
+// A failure to write the file never holds up a submit: the entry is kept in
+// memory, and the error waits for take_history_error().
+//
+// Ctrl-R searches the history backward, with the query in the split bar and
+// the matching entry in the edit area, see search.rs for the keys.
+
+use crate::{
+    CmdKind, Editor, Error, Result,
+    search::{Search, Target},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+const HISTORY_SIZE: usize = 1000;
+
+pub(crate) struct History {
+    entries: Vec<String>,
+    edits: HashMap<usize, String>, // Edited entries, and the draft at entries.len()
+    error: Option<Error>,          // Last failure to write the history file
+    file: Option<PathBuf>,         // Where the history is kept between runs
+    pos: usize,                    // Entry in the edit area, entries.len() for the draft
+    saved: usize,                  // Lines in the history file
+    size: usize,                   // Most entries kept
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            edits: HashMap::new(),
+            error: None,
+            file: None,
+            pos: 0,
+            saved: 0,
+            size: HISTORY_SIZE,
+        }
+    }
+}
+
+impl History {
+    // Add text as the newest entry, and start a new draft.  The entry is
+    // kept even when appending it to the history file fails
+    pub(crate) fn push(&mut self, text: String) -> Result<()> {
+        self.edits.clear();
+        if text.is_empty() {
+            self.pos = self.entries.len();
+            return Ok(());
+        }
+        self.entries.retain(|entry| *entry != text);
+        self.entries.push(text);
+        let excess = self.entries.len().saturating_sub(self.size);
+        self.entries.drain(..excess);
+        self.pos = self.entries.len();
+        self.append()
+    }
+
+    // Add submitted text, keeping any error writing the file for later
+    pub(crate) fn submit(&mut self, text: String) {
+        if let Err(e) = self.push(text) {
+            self.error = Some(e);
+        }
+    }
+
+    // Append the newest entry to the history file, or write the file out
+    // whole once it holds twice the entries kept
+    fn append(&mut self) -> Result<()> {
+        let (Some(file), Some(entry)) = (&self.file, self.entries.last()) else {
+            return Ok(());
+        };
+        if self.saved >= self.size.max(1) * 2 {
+            return self.save();
+        }
+        let mut line = escape(entry);
+        line.push('\n');
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(file)?
+            .write_all(line.as_bytes())?;
+        self.saved += 1;
+        Ok(())
+    }
+
+    pub(crate) fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    // Browsing the history, rather than writing the draft
+    pub(crate) fn is_browsing(&self) -> bool {
+        self.pos < self.entries.len()
+    }
+
+    // Drop the oldest entries past the size limit, forget the edits and save
+    fn reset(&mut self) -> Result<()> {
+        let excess = self.entries.len().saturating_sub(self.size);
+        self.entries.drain(..excess);
+        self.edits.clear();
+        self.pos = self.entries.len();
+        self.save()
+    }
+
+    // Add the entries in file before the ones kept, and keep the history
+    // there from now on
+    fn load(&mut self, file: PathBuf) -> Result<()> {
+        let saved = match fs::read_to_string(&file) {
+            Ok(saved) => saved,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries: Vec<String> = saved
+            .split('\n')
+            .map(unescape)
+            .filter(|entry| !entry.is_empty())
+            .chain(self.entries.drain(..))
+            .collect();
+
+        // Keep the newest of any duplicates
+        let mut seen = HashSet::new();
+        entries.reverse();
+        entries.retain(|entry| seen.insert(entry.clone()));
+        entries.reverse();
+
+        self.entries = entries;
+        self.file = Some(file);
+        self.reset()
+    }
+
+    // Write the entries to the history file, through a temporary file
+    fn save(&mut self) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let mut text = String::new();
+        for entry in &self.entries {
+            text.push_str(&escape(entry));
+            text.push('\n');
+        }
+        let mut tmp = file.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, file)?;
+        self.saved = self.entries.len();
+        Ok(())
+    }
+}
+
+// One line of the history file
+fn escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => entry.push('\n'),
+            ('\\', Some('\\')) => entry.push('\\'),
+            _ => {
+                entry.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    entry
+}
+
+impl Editor {
+    /// Add text to the input history, as if it had been submitted
+    pub fn add_history(&mut self, text: &str) -> Result<()> {
+        self.history.push(text.to_string())
+    }
+
+    /// The input history, oldest first
+    pub fn history(&self) -> &[String] {
+        &self.history.entries
+    }
+
+    // Find the next match in the input history and show it in the edit area
+    pub(crate) fn history_find(&mut self, inclusive: bool, restart: bool) -> Result<()> {
+        let Some(search) = &self.search else {
+            return Ok(());
+        };
+        let Target::Input { origin, found } = &search.target else {
+            return Ok(());
+        };
+        let entries = &self.history.entries;
+        let from = match found {
+            Some(found) if !restart => *found,
+            _ => (entries.len(), 0),
+        };
+        let shown = found.is_some();
+        let matching = |n: usize| search.matches(entries.get(n).map_or("", |entry| entry));
+        let found = if search.backward {
+            (0..=from.0).rev().find_map(|n| {
+                matching(n)
+                    .iter()
+                    .rev()
+                    .find(|m| n < from.0 || m.start < from.1 || (inclusive && m.start == from.1))
+                    .map(|m| (n, m.start))
+            })
+        } else {
+            (from.0..entries.len()).find_map(|n| {
+                matching(n)
+                    .iter()
+                    .find(|m| n > from.0 || m.start > from.1 || (inclusive && m.start == from.1))
+                    .map(|m| (n, m.start))
+            })
+        };
+        let empty = search.is_empty();
+        let (pos, text, lineidx, lidx) = origin.clone();
+
+        if let Some(search) = &mut self.search {
+            search.failing = found.is_none() && !empty;
+            if let Target::Input { found: last, .. } = &mut search.target
+                && (found.is_some() || empty)
+            {
+                *last = found;
+            }
+        }
+        match found {
+            Some((n, idx)) => {
+                if !shown {
+                    // Undo goes back to the text the search started from
+                    self.save_undo(CmdKind::Edit);
+                }
+                let entry = self.history.entries[n].clone();
+                let before = &entry[..idx];
+                let lineidx = before.matches('\n').count();
+                let lidx = idx - before.rfind('\n').map_or(0, |i| i + 1);
+                self.history_goto(n, &entry, (lineidx, lidx))
+            }
+            None if empty => self.history_goto(pos, &text, (lineidx, lidx)),
+            None => self.redraw(),
+        }
+    }
+
+    // Replace the text with history entry pos, keeping the edits to the
+    // entry being left
+    pub(crate) fn history_goto(
+        &mut self,
+        pos: usize,
+        text: &str,
+        cursor: (usize, usize),
+    ) -> Result<()> {
+        let current = self.lines.text();
+        let history = &mut self.history;
+        if history.entries.get(history.pos).map(String::as_str) == Some(&*current) {
+            history.edits.remove(&history.pos);
+        } else {
+            history.edits.insert(history.pos, current.to_string());
+        }
+        history.pos = pos;
+        self.set_text(text, cursor)
+    }
+
+    // Ctrl-P / Ctrl-N: Replace the text with the next older / newer entry,
+    // returning false when there is none
+    pub(crate) fn history_move(&mut self, older: bool) -> Result<bool> {
+        let (pos, len) = (self.history.pos, self.history.entries.len());
+        let pos = match older {
+            true if pos > 0 => pos - 1,
+            false if pos < len => pos + 1,
+            _ => return Ok(false),
+        };
+        let history = &self.history;
+        let text = match history.edits.get(&pos) {
+            Some(text) => text.clone(),
+            None => history.entries.get(pos).cloned().unwrap_or_default(),
+        };
+        self.save_undo(CmdKind::Edit);
+        let lineidx = if older {
+            0
+        } else {
+            text.split('\n').count() - 1
+        };
+        self.history_goto(pos, &text, (lineidx, usize::MAX))?;
+        Ok(true)
+    }
+
+    // Ctrl-R: Search the input history, newest first.  While the print
+    // history is frozen, search that instead
+    pub(crate) fn history_search(&mut self) -> Result<()> {
+        if self.hb_active {
+            return self.search_next(true);
+        }
+        self.anchor = None;
+        let origin = (self.history.pos, self.text(), self.lineidx, self.lidx);
+        let target = Target::Input {
+            origin,
+            found: None,
+        };
+        self.search = Some(Search::new(true, target));
+        self.redraw()
+    }
+
+    /// Keep the input history in file, adding the entries already there
+    pub fn set_history_file(&mut self, file: impl AsRef<Path>) -> Result<()> {
+        self.history.load(file.as_ref().to_path_buf())
+    }
+
+    /// Keep at most size entries in the input history, 1000 by default
+    pub fn set_history_size(&mut self, size: usize) -> Result<()> {
+        self.history.size = size;
+        self.history.reset()
+    }
+
+    /// The last error writing the history file after a submit, if any
+    pub fn take_history_error(&mut self) -> Option<Error> {
+        self.history.error.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A history file of its own for each test, removed first
+    fn temp_file(name: &str) -> PathBuf {
+        let file = std::env::temp_dir().join(format!(
+            "async-editor-{}-{name}.history",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&file);
+        file
+    }
+
+    #[test]
+    fn escape_round_trips() {
+        for entry in [
+            "plain",
+            "back\\slash",
+            "trailing\\",
+            "\\",
+            "\\\\n",
+            "two\nlines",
+            "line\n\\n\n",
+        ] {
+            let line = escape(entry);
+            assert!(!line.contains('\n'), "{line:?}");
+            assert_eq!(unescape(&line), entry);
+        }
+    }
+
+    #[test]
+    fn unescape_keeps_a_lone_backslash() {
+        assert_eq!(unescape("a\\"), "a\\");
+        assert_eq!(unescape("a\\b"), "a\\b");
+    }
+
+    #[test]
+    fn load_keeps_the_newest_duplicate() {
+        let file = temp_file("duplicates");
+        fs::write(&file, "one\ntwo\\nlines\none\nthree\n").unwrap();
+        let mut history = History::default();
+        history.push("two\nlines".to_string()).unwrap();
+        history.load(file.clone()).unwrap();
+        assert_eq!(history.entries(), ["one", "three", "two\nlines"]);
+
+        // Written back out without the duplicates
+        let saved = fs::read_to_string(&file).unwrap();
+        assert_eq!(saved, "one\nthree\ntwo\\nlines\n");
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn push_moves_a_duplicate_to_the_newest() {
+        let mut history = History::default();
+        for entry in ["a", "b", "a", ""] {
+            history.push(entry.to_string()).unwrap();
+        }
+        assert_eq!(history.entries(), ["b", "a"]);
+    }
+
+    #[test]
+    fn size_cap_drops_the_oldest() {
+        let file = temp_file("size");
+        fs::write(&file, "1\n2\n3\n4\n").unwrap();
+        let mut history = History {
+            size: 3,
+            ..History::default()
+        };
+        history.load(file.clone()).unwrap();
+        assert_eq!(history.entries(), ["2", "3", "4"]);
+
+        // Appended, then written out whole at twice the size
+        history.push("5".to_string()).unwrap();
+        history.push("6".to_string()).unwrap();
+        history.push("7".to_string()).unwrap();
+        assert_eq!(history.entries(), ["5", "6", "7"]);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "2\n3\n4\n5\n6\n7\n");
+        history.push("8".to_string()).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "6\n7\n8\n");
+
+        let mut reloaded = History {
+            size: 2,
+            ..History::default()
+        };
+        reloaded.load(file.clone()).unwrap();
+        assert_eq!(reloaded.entries(), ["7", "8"]);
+        fs::remove_file(&file).unwrap();
+    }
+}
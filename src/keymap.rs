@@ -0,0 +1,259 @@
+// Key Bindings
+//
+// Keys are looked up in a Keymap, from the key code and modifiers to an
+// Action.  Keymap::default() has the bindings listed in the README, and an
+// application can bind its own keys over them, unbind keys to ignore them,
+// or bind keys to Action::Custom(name), which handle_event passes on as
+// EditorEvent::Custom(name), or to Action::Key, passed on as the key itself in
+// EditorEvent::Key.  With set_pass_keys(true), keys that end up with no action
+// are passed on as EditorEvent::Key too.
+//
+// Keys with no binding of their own fall back, in order, to:
+//
+// - The key without Shift, when that is a motion: Shift extends the selection
+// - Typing the character, with no modifier, Shift only, or Ctrl-Alt
+//
+// Crossterm reports AltGr as Ctrl-Alt, with no way to tell the two apart, so
+// Ctrl-Alt characters are typed for the AltGr characters of international
+// layouts (@ { [ on German ones).  An application wanting a Ctrl-Alt chord
+// binds it.  Any other chord is unbound, and reaches the application with
+// set_pass_keys.
+//
+// Searching, find and replace, and vi normal mode take the keys before the
+// keymap.
+
+use crate::EditorEvent;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+
+/// Editor operations keys can be bound to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    // Motions, which extend the selection with Shift
+    Left,
+    Right,
+    Up,
+    Down,
+    WordLeft,
+    WordRight,
+    LineStart,
+    LineEnd,
+    PageUp,
+    PageDown,
+    TextStart,
+    TextEnd,
+
+    // Editing
+    Backspace,
+    Delete,
+    Newline,
+    Tab,
+    /// Complete at the cursor with the Completer, or without one insert a tab
+    Complete,
+    /// Insert the text, as if typed
+    Insert(String),
+    KillLine,
+    KillLineStart,
+    /// Kill back to the previous whitespace, or the selection
+    KillPrevWord,
+    KillWord,
+    KillWordBack,
+    Yank,
+    YankPop,
+    Undo,
+    Redo,
+    TransposeChars,
+    TransposeWords,
+    UpcaseWord,
+    DowncaseWord,
+    CapitalizeWord,
+    /// Copy the selection, with nothing selected pass on EditorEvent::CtrlC
+    Copy,
+    /// Cut the selection, with nothing selected pass on EditorEvent::CtrlX
+    Cut,
+    Paste,
+    SearchForward,
+    SearchBackward,
+    Replace,
+
+    // Screen
+    ClearScreen,
+    SplitUp,
+    SplitDown,
+    HistoryPageUp,
+    HistoryPageDown,
+    /// Search the input history backward, or the print history while it is frozen
+    HistorySearch,
+    /// Recall the previous submitted text
+    HistoryPrev,
+    /// Recall the next submitted text, or back to the draft.  Outside the
+    /// input history, pass on EditorEvent::CtrlN
+    HistoryNext,
+    /// Leave the print history, and drop the selection
+    Escape,
+
+    /// Pass the event on from handle_event
+    Emit(EditorEvent),
+    /// Application defined, passed on as EditorEvent::Custom
+    Custom(String),
+    /// Pass the key itself on as EditorEvent::Key
+    Key,
+    /// Send the text as EditorEvent::Submit and clear the edit area, or after
+    /// a backslash at the end of the line, start a new line
+    Submit,
+}
+
+impl Action {
+    /// Actions that move the cursor, and extend the selection with Shift
+    pub fn is_motion(&self) -> bool {
+        matches!(
+            self,
+            Action::Left
+                | Action::Right
+                | Action::Up
+                | Action::Down
+                | Action::WordLeft
+                | Action::WordRight
+                | Action::LineStart
+                | Action::LineEnd
+                | Action::PageUp
+                | Action::PageDown
+                | Action::TextStart
+                | Action::TextEnd
+        )
+    }
+}
+
+/// Key bindings, from key code and modifiers to an Action
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    keys: HashMap<(KeyCode, KeyModifiers), Action>,
+}
+
+impl Keymap {
+    /// A keymap without any bindings
+    pub fn new() -> Self {
+        Self {
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Bind a key, returning the action it was bound to
+    pub fn bind(&mut self, key: KeyEvent, action: Action) -> Option<Action> {
+        self.keys.insert((key.code, key.modifiers), action)
+    }
+
+    /// The action a key is bound to
+    pub fn get(&self, key: KeyEvent) -> Option<&Action> {
+        self.keys.get(&(key.code, key.modifiers))
+    }
+
+    /// Bind Enter to Submit, and Alt-Enter / Shift-Enter to Newline, or back
+    pub fn set_submit_mode(&mut self, submit: bool) {
+        let enter = |modifiers| KeyEvent::new(KeyCode::Enter, modifiers);
+        if submit {
+            self.bind(enter(KeyModifiers::NONE), Action::Submit);
+            self.bind(enter(KeyModifiers::ALT), Action::Newline);
+            self.bind(enter(KeyModifiers::SHIFT), Action::Newline);
+        } else {
+            self.bind(enter(KeyModifiers::NONE), Action::Newline);
+            self.unbind(enter(KeyModifiers::ALT));
+            self.unbind(enter(KeyModifiers::SHIFT));
+        }
+    }
+
+    /// Remove a binding, returning the action it was bound to
+    pub fn unbind(&mut self, key: KeyEvent) -> Option<Action> {
+        self.keys.remove(&(key.code, key.modifiers))
+    }
+
+    /// The action for a key, and whether Shift extends the selection
+    pub(crate) fn action(&self, code: KeyCode, modifiers: KeyModifiers) -> Option<(Action, bool)> {
+        let get = |modifiers| self.keys.get(&(code, modifiers)).cloned();
+        if let Some(action) = get(modifiers) {
+            return Some((action, false));
+        }
+        if modifiers.contains(KeyModifiers::SHIFT)
+            && let Some(action) = get(modifiers - KeyModifiers::SHIFT)
+        {
+            let select = action.is_motion();
+            return Some((action, select));
+        }
+        // Ctrl-Alt may be AltGr
+        let typed = modifiers - KeyModifiers::SHIFT;
+        match code {
+            KeyCode::Char(c)
+                if typed.is_empty() || typed == KeyModifiers::CONTROL | KeyModifiers::ALT =>
+            {
+                Some((Action::Insert(c.to_string()), false))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for Keymap {
+    /// The bindings described in the README
+    fn default() -> Self {
+        const NONE: KeyModifiers = KeyModifiers::NONE;
+        const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+        const ALT: KeyModifiers = KeyModifiers::ALT;
+        let bindings = [
+            (KeyCode::Left, NONE, Action::Left),
+            (KeyCode::Right, NONE, Action::Right),
+            (KeyCode::Up, NONE, Action::Up),
+            (KeyCode::Down, NONE, Action::Down),
+            (KeyCode::Left, CTRL, Action::WordLeft),
+            (KeyCode::Right, CTRL, Action::WordRight),
+            (KeyCode::Home, NONE, Action::LineStart),
+            (KeyCode::Char('a'), CTRL, Action::LineStart),
+            (KeyCode::End, NONE, Action::LineEnd),
+            (KeyCode::Char('e'), CTRL, Action::LineEnd),
+            (KeyCode::PageUp, NONE, Action::PageUp),
+            (KeyCode::PageDown, NONE, Action::PageDown),
+            (KeyCode::Home, CTRL, Action::TextStart),
+            (KeyCode::End, CTRL, Action::TextEnd),
+            (KeyCode::Backspace, NONE, Action::Backspace),
+            (KeyCode::Delete, NONE, Action::Delete),
+            (KeyCode::Enter, NONE, Action::Newline),
+            (KeyCode::Tab, NONE, Action::Complete),
+            (KeyCode::Char('k'), CTRL, Action::KillLine),
+            (KeyCode::Char('u'), CTRL, Action::KillLineStart),
+            (KeyCode::Char('w'), CTRL, Action::KillPrevWord),
+            (KeyCode::Char('d'), ALT, Action::KillWord),
+            (KeyCode::Backspace, ALT, Action::KillWordBack),
+            (KeyCode::Char('y'), CTRL, Action::Yank),
+            (KeyCode::Char('y'), ALT, Action::YankPop),
+            (KeyCode::Char('z'), CTRL, Action::Undo),
+            (KeyCode::Char('z'), ALT, Action::Redo),
+            (KeyCode::Char('t'), CTRL, Action::TransposeChars),
+            (KeyCode::Char('t'), ALT, Action::TransposeWords),
+            (KeyCode::Char('u'), ALT, Action::UpcaseWord),
+            (KeyCode::Char('l'), ALT, Action::DowncaseWord),
+            (KeyCode::Char('c'), ALT, Action::CapitalizeWord),
+            (KeyCode::Char('c'), CTRL, Action::Copy),
+            (KeyCode::Char('x'), CTRL, Action::Cut),
+            (KeyCode::Char('v'), CTRL, Action::Paste),
+            (KeyCode::Char('f'), CTRL, Action::SearchForward),
+            (KeyCode::Char('b'), CTRL, Action::SearchBackward),
+            (KeyCode::Char('r'), CTRL, Action::HistorySearch),
+            (KeyCode::Char('%'), ALT, Action::Replace),
+            (KeyCode::Char('l'), CTRL, Action::ClearScreen),
+            (KeyCode::Up, CTRL, Action::SplitUp),
+            (KeyCode::Down, CTRL, Action::SplitDown),
+            (KeyCode::PageUp, CTRL, Action::HistoryPageUp),
+            (KeyCode::PageDown, CTRL, Action::HistoryPageDown),
+            (KeyCode::Esc, NONE, Action::Escape),
+            (KeyCode::Char('d'), CTRL, Action::Emit(EditorEvent::CtrlD)),
+            (KeyCode::Char('p'), CTRL, Action::HistoryPrev),
+            (KeyCode::Char('n'), CTRL, Action::HistoryNext),
+            (KeyCode::Char('q'), CTRL, Action::Emit(EditorEvent::CtrlQ)),
+            (KeyCode::Char('s'), CTRL, Action::Emit(EditorEvent::CtrlS)),
+        ];
+        let mut keymap = Self::new();
+        for (code, modifiers, action) in bindings {
+            keymap.bind(KeyEvent::new(code, modifiers), action);
+        }
+        keymap
+    }
+}
@@ -0,0 +1,163 @@
+// Emacs style Kill Ring
+//
+// Killed text is saved on the ring, newest first.  Consecutive kills are
+// joined into one entry the way readline does it: forward kills append,
+// backward kills prepend.  Ctrl-Y yanks the newest entry, and Alt-Y
+// right after a yank swaps it for the next older one.
+
+use crate::{CmdKind, Editor, Result, words::word_ranges};
+use grapheme_utils::*;
+use std::collections::VecDeque;
+
+const KILL_RING_SIZE: usize = 30;
+
+#[derive(Default)]
+pub(crate) struct KillRing {
+    kills: VecDeque<String>,
+    yank_idx: usize,            // Entry inserted by the last yank
+    yank_start: (usize, usize), // Where the last yank was inserted
+}
+
+impl KillRing {
+    fn push(&mut self, text: String, join: bool, backward: bool) {
+        match self.kills.front_mut() {
+            Some(kill) if join => {
+                if backward {
+                    kill.insert_str(0, &text);
+                } else {
+                    kill.push_str(&text);
+                }
+            }
+            _ => {
+                self.kills.push_front(text);
+                self.kills.truncate(KILL_RING_SIZE);
+            }
+        }
+    }
+}
+
+impl Editor {
+    /// Delete the text from start to end onto the kill ring
+    pub(crate) fn kill(
+        &mut self,
+        start: (usize, usize),
+        end: (usize, usize),
+        backward: bool,
+    ) -> Result<()> {
+        if start == end {
+            return Ok(());
+        }
+        let join = self.last_cmd == CmdKind::Kill;
+        self.save_undo(CmdKind::Kill);
+        let text = self.delete_range(start, end)?;
+        self.kill_ring.push(text, join, backward);
+        self.redraw()?;
+        Ok(())
+    }
+
+    // Ctrl-K: Kill to the end of the line, or the line break when already there
+    pub(crate) fn kill_line(&mut self) -> Result<()> {
+        self.anchor = None;
+        let start = self.clamp_pos((self.lineidx, self.lidx));
+        let end = if start.1 == self.lines[start.0].len() && start.0 + 1 < self.lines.len() {
+            (start.0 + 1, 0)
+        } else {
+            (start.0, self.lines[start.0].len())
+        };
+        self.kill(start, end, false)
+    }
+
+    // Ctrl-U: Kill to the start of the line
+    pub(crate) fn kill_line_start(&mut self) -> Result<()> {
+        self.anchor = None;
+        let end = self.clamp_pos((self.lineidx, self.lidx));
+        self.kill((end.0, 0), end, true)
+    }
+
+    // Ctrl-W: Kill the selection, otherwise back to the previous whitespace
+    pub(crate) fn kill_prev_word(&mut self) -> Result<()> {
+        if let Some((start, end)) = self.selection_range() {
+            self.anchor = None;
+            return self.kill(start, end, false);
+        }
+        self.anchor = None;
+        let end = self.clamp_pos((self.lineidx, self.lidx));
+        let (mut lineidx, mut idx) = end;
+
+        // Skip whitespace, including line breaks
+        loop {
+            if idx == 0 {
+                if lineidx == 0 {
+                    break;
+                }
+                lineidx -= 1;
+                idx = self.lines[lineidx].len();
+                continue;
+            }
+            let prev = prev_grapheme_idx_from_idx(&self.lines[lineidx], idx);
+            if !grapheme_at_idx(&self.lines[lineidx], prev)
+                .chars()
+                .all(char::is_whitespace)
+            {
+                break;
+            }
+            idx = prev;
+        }
+        while idx > 0 {
+            let prev = prev_grapheme_idx_from_idx(&self.lines[lineidx], idx);
+            if grapheme_at_idx(&self.lines[lineidx], prev)
+                .chars()
+                .all(char::is_whitespace)
+            {
+                break;
+            }
+            idx = prev;
+        }
+        self.kill((lineidx, idx), end, true)
+    }
+
+    // Alt-D: Kill to the end of the word at or after the cursor
+    pub(crate) fn kill_word(&mut self) -> Result<()> {
+        self.anchor = None;
+        let start = self.clamp_pos((self.lineidx, self.lidx));
+        let end = self.next_word_end(start, word_ranges);
+        self.kill(start, end, false)
+    }
+
+    // Alt-Backspace: Kill back to the start of the word before the cursor
+    pub(crate) fn kill_word_back(&mut self) -> Result<()> {
+        self.anchor = None;
+        let end = self.clamp_pos((self.lineidx, self.lidx));
+        let start = self.prev_word_start(end, word_ranges);
+        self.kill(start, end, true)
+    }
+
+    // Ctrl-Y: Insert the newest kill
+    pub(crate) fn yank(&mut self) -> Result<()> {
+        self.delete_selection()?;
+        let Some(text) = self.kill_ring.kills.front().cloned() else {
+            return Ok(());
+        };
+        self.save_undo(CmdKind::Yank);
+        self.kill_ring.yank_idx = 0;
+        self.kill_ring.yank_start = self.clamp_pos((self.lineidx, self.lidx));
+        self.insert_text(&text)?;
+        self.redraw()?;
+        Ok(())
+    }
+
+    // Alt-Y: Replace the text just yanked with the next older kill
+    pub(crate) fn yank_pop(&mut self) -> Result<()> {
+        if self.last_cmd != CmdKind::Yank || self.kill_ring.kills.is_empty() {
+            return Ok(());
+        }
+        self.save_undo(CmdKind::Yank);
+        let end = self.clamp_pos((self.lineidx, self.lidx));
+        self.delete_range(self.kill_ring.yank_start, end)?;
+        self.kill_ring.yank_idx = (self.kill_ring.yank_idx + 1) % self.kill_ring.kills.len();
+        let text = self.kill_ring.kills[self.kill_ring.yank_idx].clone();
+        self.insert_text(&text)?;
+        self.redraw()?;
+        Ok(())
+    }
+}
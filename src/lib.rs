@@ -4,18 +4,74 @@
 //! Keyboard Commands:
 //!
 //! - Arrows, PgUp, PgDn => Move
-//! todo - Ctrl-W: Erase the input from the cursor to the previous whitespace
-//! todo - Ctrl-U: Erase the input before the cursor
+//! - Ctrl-W: Kill the selection, or the input from the cursor to the previous whitespace
+//! - Ctrl-U: Kill the input before the cursor
+//! - Ctrl-K: Kill the input after the cursor, or the line break at the end of the line
+//! - Ctrl-Y / Alt-Y: Yank the last kill / Replace it with the next older kill
+//! - Alt-D / Alt-Backspace: Kill to the end of the next word / the start of the previous word
+//! - Ctrl-T / Alt-T: Transpose the characters / words around the cursor
+//! - Alt-U / Alt-L / Alt-C: Upcase / Downcase / Capitalize to the end of the word
 //! - Ctrl-L: Clear the screen
-//! - Ctrl-Left / Ctrl-Right: Move to previous/next word
+//! - Ctrl-Left / Ctrl-Right: Move to previous/next word, on Unicode word boundaries
 //! - Home: Jump to the start of the line
 //! - End: Jump to the end of the line
 //! - Ctrl-Up, Ctrl-Down:  Contract / Expand Print vs Edit windows
 //! - Ctrl-C: Ignored
 //!   Ctrl Left/Right => Move Left/Right by Word
 //! - Ctrl PgUp / PgDn - Print History Scrollback, ESC to exit.
-//! in dev - Ctrl-k => Delete current line
-//!   Ctrl-C, Ctrl-D, Ctrl-Q, Ctrl-X => Exit/Quit
+//! - Ctrl-Z / Alt-Z: Undo / Redo
+//! - Shift + Arrows, Home, End, PgUp, PgDn, Ctrl-Left/Right: Select text
+//! - Ctrl-C / Ctrl-X / Ctrl-V: Copy / Cut / Paste the selection (Ctrl-C and Ctrl-X are
+//!   passed on as EditorEvents when nothing is selected)
+//! - Terminal paste: Inserted in one step using bracketed paste
+//! - Ctrl-F / Ctrl-B: Search forward / backward, then Ctrl-F / Ctrl-B for the next / previous match.
+//!   Enter keeps the cursor at the match, Esc returns to the start.  Alt-R toggles regex.
+//!   While the print history is frozen (Ctrl-PgUp), Ctrl-F / Ctrl-B search the print history
+//! - Ctrl-R: Reverse search the input history, showing the matching entry in the edit area.
+//!   Ctrl-R / Ctrl-F for the next older / newer match, Enter keeps it, Esc brings back the draft
+//! - Alt-%: Find and replace from the cursor (literal or regex, Alt-R), confirming each
+//!   match with y/n, ! for all the rest, . for this one and stop, q to quit.  One undo step
+//! - Ctrl-P / Ctrl-N, or Up on the first line / Down on the last line: Recall the previous /
+//!   next submitted text.  Edits to recalled text are kept until the next submit, and the text
+//!   being written is kept as the draft after the newest entry.  Ctrl-N is passed on as
+//!   EditorEvent::CtrlN when not in the history
+//! - Ctrl-C, Ctrl-D, Ctrl-Q, Ctrl-X => Exit/Quit
+//!
+//! Every key above can be rebound: keymap() returns the Keymap from keys to Actions,
+//! and keys bound to Action::Custom(name) are passed on as EditorEvent::Custom(name).
+//! Keys bound to Action::Key are passed on as EditorEvent::Key(key), as are all the keys
+//! without a binding after set_pass_keys(true).
+//!
+//! Options:
+//!
+//! - set_soft_wrap(true): Wrap long lines onto several rows instead of scrolling sideways
+//! - set_expand_tab(true): Tab inserts spaces up to the next tab stop
+//! - set_auto_indent(true): Enter copies the leading whitespace of the line
+//! - set_tabstop(n): Change the tab stop width
+//! - set_line_numbers(true): Show line numbers left of the edit area
+//! - set_word_chars("_-"): Characters besides letters and digits that make up words
+//! - set_completer(completer): Tab completes at the cursor.  Several candidates are shown in
+//!   a popup, Up / Down to choose, Enter or Tab to accept, Esc to close
+//! - set_highlighter(highlighter): Style the text, with state carried from line to line for
+//!   things like fenced code blocks.  MarkdownHighlighter highlights headings and code
+//! - set_hinter(hinter): Show a hint dimmed after the cursor at the end of the line, Right or
+//!   End to accept it.  HistoryHinter hints from the input history
+//! - set_history_file(path): Keep the input history in a file between runs, saved after every
+//!   submit.  set_history_size(n) limits it to the newest n entries (1000 by default).  A
+//!   failure to save does not stop the submit, take_history_error() returns it
+//! - set_submit_mode(true): Enter clears the edit area, and async_editor() returns the text
+//!   as EditorEvent::Submit(text).  Alt-Enter, Shift-Enter (where the terminal reports it)
+//!   or a backslash at the end of the line start a new line instead
+//! - set_validator(validator): Check the text before it is submitted.  Validation::Incomplete
+//!   makes Enter start a new line, Validation::Invalid keeps the text, showing the message in
+//!   the split bar and marking the offending line
+//! - set_vi_mode(true): Vi style modal editing.  Esc switches from insert to normal mode,
+//!   with the motions hjkl w b e 0 ^ $ gg G, the operators d c y, counts, . to repeat
+//!   the last change and v for visual mode.  The mode is shown in the split bar
+//!
+//! handle() returns an EditorHandle, a Send + Clone handle for other tasks to set_text,
+//! insert_at_cursor, clear, move_cursor, and read the cursor() and text() of the edit area.
+//! Its commands are carried out by async_editor(), between keys.
 //!
 //! Note: this works, but doctest will fail, so doc test have been disabled in Cargo.toml
 //! ```rust
@@ -32,27 +88,30 @@
 //!   let mut count: u32 = 0;
 //!
 //!   loop {
-//! 	tokio::select! {
-//! 	_ = sleep(Duration::from_millis(200)) => {
-//! 		count += 1;
-//! 		//write!(async_editor_stdout, "{}", format!("_Message {}\nreceived\n!", count))?;
-//! 		write!(async_editor_stdout, "{}", format!("_Message {} received!", count))?;
-//! 	}
-//! 	cmd = async_editor.async_editor() => match cmd {
-//! 		Ok(EditorEvent::CtrlC | EditorEvent::CtrlD | EditorEvent::CtrlQ | EditorEvent::CtrlX) => {
-//! 			break;
-//! 		}
-//! 		Ok(EditorEvent::CtrlS) => {
-//! 			writeln!(async_editor_stdout, "\n\nCtrlS\n")?;
-//! 		}
-//! 		Ok(_) => {continue;}
-//! 			Err(e) => {
-//! 				writeln!(async_editor_stdout, "\n\nError: {e:?}\n")?;
-//! 				break;
-//! 			}
-//! 		}
-//! 	}
-//! 	async_editor.flush()?;
+//!     tokio::select! {
+//!     _ = sleep(Duration::from_millis(200)) => {
+//!         count += 1;
+//!         //write!(async_editor_stdout, "{}", format!("_Message {}\nreceived\n!", count))?;
+//!         write!(async_editor_stdout, "{}", format!("_Message {} received!", count))?;
+//!     }
+//!     cmd = async_editor.async_editor() => match cmd {
+//!         Ok(EditorEvent::CtrlC | EditorEvent::CtrlD | EditorEvent::CtrlQ | EditorEvent::CtrlX) => {
+//!             break;
+//!         }
+//!         Ok(EditorEvent::CtrlS) => {
+//!             writeln!(async_editor_stdout, "\n\nCtrlS\n")?;
+//!         }
+//!         Ok(EditorEvent::Submit(text)) => {
+//!             writeln!(async_editor_stdout, "\n\n{text}\n")?;
+//!         }
+//!         Ok(_) => {continue;}
+//!             Err(e) => {
+//!                 writeln!(async_editor_stdout, "\n\nError: {e:?}\n")?;
+//!                 break;
+//!             }
+//!         }
+//!     }
+//!     async_editor.flush()?;
 //!   }
 //!   async_editor.flush()?;
 //!
@@ -72,8 +131,10 @@
 use crossterm::{
     QueueableCommand,
     cursor::{self, position},
-    event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
-    style::Print,
+    event::{
+        DisableBracketedPaste, EnableBracketedPaste, Event, EventStream, KeyEvent, KeyEventKind,
+    },
+    style::{Attribute, Color, ContentStyle, Print, PrintStyledContent, StyledContent},
     terminal::{self, disable_raw_mode},
 };
 use futures_util::{FutureExt, StreamExt, select};
@@ -81,19 +142,50 @@ use grapheme_utils::*;
 use historybuffer::HistoryBuffer;
 use std::{
     io::{self, Stdout, Write, stdout},
-    ops::DerefMut,
+    ops::{DerefMut, Range},
+    path::Path,
     rc::Rc,
     string::String,
 };
 use thingbuf::mpsc::{Receiver, Sender, errors::TrySendError};
 use unicode_segmentation::UnicodeSegmentation;
 
+mod complete;
 mod error;
-pub use self::error::{Error, Result};
+mod handle;
+mod highlight;
+mod hint;
+mod history;
+mod keymap;
+mod killring;
+mod replace;
+mod rope;
+mod scrollback;
+mod search;
+mod selection;
+mod undo;
+mod validate;
+mod vi;
+mod words;
+mod wrap;
+use self::{
+    complete::Completion, handle::Command, highlight::Highlights, history::History,
+    killring::KillRing, replace::Replace, rope::Rope, search::Search, undo::UndoStack,
+    validate::Invalid, vi::Vi, words::Case,
+};
+pub use self::{
+    complete::{Candidate, Completer},
+    error::{Error, Result},
+    handle::EditorHandle,
+    highlight::{Highlighter, MarkdownHighlighter},
+    hint::{Hinter, HistoryHinter},
+    keymap::{Action, Keymap},
+    validate::{Validation, Validator},
+};
 
 const HISTORY_BUFFER_SIZE: usize = 300 * 160 * 4;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum EditorEvent {
     CtrlC,
     CtrlD,
@@ -101,6 +193,13 @@ pub enum EditorEvent {
     CtrlN,
     CtrlS,
     CtrlX,
+    /// A key bound to Action::Custom
+    Custom(String),
+    /// A key bound to Action::Key, or any key the editor has no use for
+    /// when set_pass_keys is on
+    Key(KeyEvent),
+    /// The text of the edit area, sent with Enter in submit mode
+    Submit(String),
 }
 
 pub enum WriteHistoryType {
@@ -109,6 +208,18 @@ pub enum WriteHistoryType {
     Quit,
 }
 
+// What the last key did, so commands can chain (i.e. group typing into one undo)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CmdKind {
+    Other,
+    Insert,   // Typing
+    Delete,   // Backspace / Delete
+    Edit,     // Any other change to the lines
+    Kill,     // Ctrl-K, Ctrl-U, Ctrl-W ...
+    Yank,     // Ctrl-Y, Alt-Y
+    Vertical, // Up, Down, PgUp, PgDn - Keep the goal column
+}
+
 /// AsyncEditor - Multiline Terminal Editor with simultaneous stdout
 ///
 /// AsyncEditor is a functional multline editor supporting standard
@@ -120,9 +231,11 @@ pub enum WriteHistoryType {
 //
 // The main AsyncEditor struct functions as a ReadWriteRouter
 pub struct AsyncEditor {
-    event_stream: EventStream,    // Crossterm Event Stream
-    stdout_rx: Receiver<Vec<u8>>, // Stdout pipe
-    editor: Editor,               // Multiline Editor
+    command_rx: Receiver<Command>, // EditorHandle commands
+    command_tx: Sender<Command>,   // Cloned into each EditorHandle
+    event_stream: EventStream,     // Crossterm Event Stream
+    stdout_rx: Receiver<Vec<u8>>,  // Stdout pipe
+    editor: Editor,                // Multiline Editor
 }
 
 impl AsyncEditor {
@@ -135,21 +248,25 @@ impl AsyncEditor {
         tabstop: u8,
     ) -> Result<(Self, SharedStdout)> {
         let (stdout_tx, stdout_rx) = thingbuf::mpsc::channel(500);
+        let (command_tx, command_rx) = thingbuf::mpsc::channel(50);
 
         let editor = Editor::new(initial_content, split_prompt, print_height, tabstop)?;
 
         let mut async_editor = AsyncEditor {
+            command_rx,
+            command_tx,
             event_stream: EventStream::new(),
             stdout_rx,
             editor,
         };
         async_editor.editor.term.queue(terminal::EnableLineWrap)?;
+        async_editor.editor.term.queue(EnableBracketedPaste)?;
         async_editor.editor.term.flush()?;
         Ok((
             async_editor,
             SharedStdout {
                 buf: Vec::new(),
-                stdout_tx: stdout_tx,
+                stdout_tx,
             },
         ))
     }
@@ -188,17 +305,179 @@ impl AsyncEditor {
                     },
                     None => return Err(Error::SharedStdoutClosed),
                 },
+                command = self.command_rx.recv().fuse() => if let Some(command) = command {
+                    self.editor.run_command(command)?;
+                    self.editor.term.flush()?;
+                },
             }
         }
     }
 
+    /// Add text to the input history, as if it had been submitted
+    pub fn add_history(&mut self, text: &str) -> Result<()> {
+        self.editor.add_history(text)
+    }
+
+    /// A handle to control the edit area from other tasks
+    pub fn handle(&self) -> EditorHandle {
+        EditorHandle {
+            command_tx: self.command_tx.clone(),
+        }
+    }
+
+    /// The input history, oldest first
+    pub fn history(&self) -> &[String] {
+        self.editor.history()
+    }
+
+    /// Replace every match of pattern with replacement, as one undo step
+    ///
+    /// With regex, $1 or ${name} in the replacement expand to capture groups.
+    /// Returns the number of replacements.
+    pub fn replace_all(&mut self, pattern: &str, replacement: &str, regex: bool) -> Result<usize> {
+        let count = self.editor.replace_all(pattern, replacement, regex)?;
+        self.editor.term.flush()?;
+        Ok(count)
+    }
+
     pub fn text(&self) -> String {
         self.editor.text()
     }
+
+    /// Wrap long lines onto several rows instead of scrolling sideways
+    pub fn set_soft_wrap(&mut self, soft_wrap: bool) -> Result<()> {
+        self.editor.set_soft_wrap(soft_wrap)?;
+        self.editor.term.flush()?;
+        Ok(())
+    }
+
+    /// Start new lines with the leading whitespace of the line above
+    pub fn set_auto_indent(&mut self, auto_indent: bool) {
+        self.editor.set_auto_indent(auto_indent);
+    }
+
+    /// Complete with completer on Tab, showing a popup when there are several candidates
+    pub fn set_completer(&mut self, completer: impl Completer + 'static) {
+        self.editor.set_completer(completer);
+    }
+
+    /// Insert spaces up to the next tab stop instead of a tab
+    pub fn set_expand_tab(&mut self, expand_tab: bool) {
+        self.editor.set_expand_tab(expand_tab);
+    }
+
+    /// The key bindings, to bind or unbind keys
+    pub fn keymap(&mut self) -> &mut Keymap {
+        self.editor.keymap()
+    }
+
+    /// Replace the key bindings
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.editor.set_keymap(keymap);
+    }
+
+    /// Style the text in the edit area with highlighter
+    pub fn set_highlighter(&mut self, highlighter: impl Highlighter + 'static) -> Result<()> {
+        self.editor.set_highlighter(highlighter)?;
+        self.editor.term.flush()?;
+        Ok(())
+    }
+
+    /// Show hints from hinter dimmed after the cursor, Right or End accept them
+    pub fn set_hinter(&mut self, hinter: impl Hinter + 'static) {
+        self.editor.set_hinter(hinter);
+    }
+
+    /// Keep the input history in file, loading the entries already there
+    pub fn set_history_file(&mut self, file: impl AsRef<Path>) -> Result<()> {
+        self.editor.set_history_file(file)
+    }
+
+    /// Keep at most size entries in the input history, 1000 by default
+    pub fn set_history_size(&mut self, size: usize) -> Result<()> {
+        self.editor.set_history_size(size)
+    }
+
+    /// The last error writing the history file after a submit, if any.
+    /// Submitting goes on regardless, with the entry kept in memory
+    pub fn take_history_error(&mut self) -> Option<Error> {
+        self.editor.take_history_error()
+    }
+
+    /// Show line numbers left of the edit area
+    pub fn set_line_numbers(&mut self, line_numbers: bool) -> Result<()> {
+        self.editor.set_line_numbers(line_numbers)?;
+        self.editor.term.flush()?;
+        Ok(())
+    }
+
+    /// Pass on keys without a binding as EditorEvent::Key
+    pub fn set_pass_keys(&mut self, pass_keys: bool) {
+        self.editor.set_pass_keys(pass_keys);
+    }
+
+    /// Enter sends the text as EditorEvent::Submit, Alt-Enter starts a new line
+    pub fn set_submit_mode(&mut self, submit: bool) {
+        self.editor.set_submit_mode(submit);
+    }
+
+    pub fn set_tabstop(&mut self, tabstop: u8) -> Result<()> {
+        self.editor.set_tabstop(tabstop)?;
+        self.editor.term.flush()?;
+        Ok(())
+    }
+
+    /// Check the text with validator before it is submitted: Incomplete text gets a new
+    /// line, Invalid text stays with the message in the split bar
+    pub fn set_validator(&mut self, validator: impl Validator + 'static) {
+        self.editor.set_validator(validator);
+    }
+
+    /// Vi style modal editing, starting in insert mode
+    pub fn set_vi_mode(&mut self, vi_mode: bool) -> Result<()> {
+        self.editor.set_vi_mode(vi_mode)?;
+        self.editor.term.flush()?;
+        Ok(())
+    }
+
+    /// Characters besides letters and digits that make up words, "_" by default
+    pub fn set_word_chars(&mut self, word_chars: &str) {
+        self.editor.set_word_chars(word_chars);
+    }
+}
+
+// Display width of the text in st, with tabs measured from the start of st
+fn display_width(st: &str, tabstop: usize) -> usize {
+    st.graphemes(true)
+        .fold(0, |width, g| width + tab_width(g, width, tabstop))
+}
+
+// Style of the grapheme at idx, merging every styled range covering it
+fn grapheme_style(styles: &[(Range<usize>, ContentStyle)], idx: usize) -> ContentStyle {
+    let mut style = ContentStyle::new();
+    for (_, s) in styles.iter().filter(|(range, _)| range.contains(&idx)) {
+        style.foreground_color = s.foreground_color.or(style.foreground_color);
+        style.background_color = s.background_color.or(style.background_color);
+        style.underline_color = s.underline_color.or(style.underline_color);
+        style.attributes.extend(s.attributes);
+    }
+    style
+}
+
+// Index of the grapheme covering display column col of st, or st.len()
+fn idx_at_col(st: &str, col: usize, tabstop: usize) -> usize {
+    let mut width = 0;
+    for (i, g) in st.grapheme_indices(true) {
+        width += tab_width(g, width, tabstop);
+        if width > col {
+            return i;
+        }
+    }
+    st.len()
 }
 
 fn string_to_hex(s: &str, maxlen: usize) -> String {
-    let mut new_hex_string = String::with_capacity(s.as_bytes().len() * 2);
+    let mut new_hex_string = String::with_capacity(s.len() * 2);
 
     for byte in s.as_bytes().iter() {
         let s = format!("{:02x} ", byte);
@@ -210,28 +489,66 @@ fn string_to_hex(s: &str, maxlen: usize) -> String {
     new_hex_string
 }
 
+// Width of grapheme g starting at display column width
+fn tab_width(g: &str, width: usize, tabstop: usize) -> usize {
+    if g == "\t" {
+        tabstop - (width % tabstop)
+    } else {
+        string_width(g)
+    }
+}
+
 pub struct Editor {
-    curx: u16, // Grapheme Cursor Position
+    anchor: Option<(usize, usize)>, // Selection start (lineidx, lidx)
+    auto_indent: bool,              // Enter copies the leading whitespace
+    clipboard: String,
+    completer: Option<Box<dyn Completer>>, // Tab completion source
+    completion: Option<Completion>,        // Completion popup, when open
+    curx: u16,                             // Grapheme Cursor Position
     cury: u16,
+    expand_tab: bool, // Tab inserts spaces
+    goal_col: usize,  // Display column to return to while moving up and down
     hb_active: bool,
     hb_start_index: usize,
     hb_end_index: usize,
+    highlighter: Option<Box<dyn Highlighter>>,
+    highlights: Highlights, // Highlighter styles and states of the lines drawn
+    hint: Option<String>,   // Inline hint shown after the cursor
+    hinter: Option<Box<dyn Hinter>>, // Inline hint source
     histbuf: HistoryBuffer, // Make the buffer large enough to hold a huge terminal window screen with LOTS of escape characters
+    history: History,       // Input history
+    invalid: Option<Invalid>, // Why the text was not submitted, until the next key
+    keymap: Keymap,
+    kill_ring: KillRing,
+    last_cmd: CmdKind, // What the previous key did
+    last_search: String,
     lidx: usize,
-    lines: Vec<String>, // Editor text without \n
+    line_numbers: bool, // Draw the line number gutter
+    lines: Rope,        // Editor text without \n
     lineidx: usize,     // Which line active
     lofs: usize,
     loose_cursor: bool, // Detects when we've moved off a long line.
+    pass_keys: bool,    // Pass on keys without a binding as EditorEvent::Key
     printlines: u16,    // Number of Lines used printing
     printx: u16,        // print cursor pos
     printy: u16,
+    replace: Option<Replace>, // Find and replace in progress
     scrollstart: usize,
-    sizex: u16, // screen size
+    search: Option<Search>, // Incremental search in progress
+    sizex: u16,             // screen size
     sizey: u16,
+    soft_wrap: bool, // Wrap long lines instead of scrolling them
     split_prompt: String,
     tabstop: u8,
     term: Stdout,
+    this_cmd: CmdKind, // What the current key is doing
     tmpbuf: Rc<String>,
+    undo_stack: UndoStack,
+    validator: Option<Box<dyn Validator>>, // Checks the text before it is submitted
+    vi: Option<Vi>,                        // Vi mode, when on
+    word_chars: String, // Extra characters that make up words, besides letters and digits
+    wrapped: Option<(usize, i32, usize)>, // Soft Wrap: Cursor line as drawn, its screen row and row count
+    wraptop: usize,                       // Soft Wrap: First row of the scrollstart line on screen
 }
 
 impl Editor {
@@ -244,312 +561,261 @@ impl Editor {
         let term = stdout();
         let (sizex, sizey) = terminal::size()?;
         let (_curx, cury) = position()?;
-        let newprintlines = (sizey as f32 * print_height.max(0.1).min(0.9)) as u16;
+        let newprintlines = (sizey as f32 * print_height.clamp(0.1, 0.9)) as u16;
         terminal::enable_raw_mode()?;
 
         Ok(Self {
+            anchor: None,
+            auto_indent: false,
+            clipboard: String::new(),
+            completer: None,
+            completion: None,
             curx: 0,
             cury: newprintlines + 2,
+            expand_tab: false,
+            goal_col: 0,
             hb_active: false,
             hb_start_index: 0,
             hb_end_index: 0,
+            highlighter: None,
+            highlights: Highlights::default(),
+            hint: None,
+            hinter: None,
             histbuf: HistoryBuffer::new(HISTORY_BUFFER_SIZE), // Make the buffer large enough to hold a huge terminal window screen with LOTS of escape characters
+            history: History::default(),
+            invalid: None,
+            keymap: Keymap::default(),
+            kill_ring: KillRing::default(),
+            last_cmd: CmdKind::Other,
+            last_search: String::new(),
             lidx: 0, // line index of grapheme at the cursor
+            line_numbers: false,
             lines: initial_content.split("\n").map(|s| s.to_string()).collect(), // convert_tabs(s,'→',8).to_string()).collect(),  // Exlusive \n makes a few painful things easier
             lineidx: 0,
             lofs: 0, // line index offset to the start of the displayed text
             loose_cursor: false,
+            pass_keys: false,
             printlines: newprintlines,
             printx: 0,
             printy: cury + 1,
+            replace: None,
             scrollstart: 0,
-            sizex: sizex,
-            sizey: sizey,
-            split_prompt: split_prompt,
-            tabstop: tabstop,
-            term: term,
+            search: None,
+            sizex,
+            sizey,
+            soft_wrap: false,
+            split_prompt,
+            tabstop,
+            term,
+            this_cmd: CmdKind::Other,
             tmpbuf: Rc::new(String::new()), // with_capacity(BUFFER_SIZE)), not needed...  Grows to largest need, then reused
+            undo_stack: UndoStack::default(),
+            validator: None,
+            vi: None,
+            word_chars: "_".to_string(),
+            wrapped: None,
+            wraptop: 0,
         })
     }
 
-    fn ch(&self, idx: usize) -> char {
-        grapheme_at_idx(&self.lines[self.lineidx], idx)
-            .chars()
-            .next()
-            .unwrap_or('\0')
+    // Clamp a (lineidx, idx) position into the text, on a grapheme boundary
+    fn clamp_pos(&self, pos: (usize, usize)) -> (usize, usize) {
+        let lineidx = pos.0.min(self.lines.len().saturating_sub(1));
+        (lineidx, grapheme_idx_at_idx(&self.lines[lineidx], pos.1))
     }
 
-    fn grapheme_idx_at_idx(&self, idx: usize) -> usize {
-        grapheme_idx_at_idx(&self.lines[self.lineidx], idx)
+    // Remove the text from start to end, leaving the cursor at start
+    fn delete_range(&mut self, start: (usize, usize), end: (usize, usize)) -> Result<String> {
+        let removed = if start.0 == end.0 {
+            self.lines[start.0].drain(start.1..end.1).collect()
+        } else {
+            let tail = self.lines[end.0].split_off(end.1);
+            let middle = self.lines.drain(start.0 + 1..end.0 + 1);
+            let mut removed = self.lines[start.0].split_off(start.1);
+            self.lines[start.0].push_str(&tail);
+            for line in middle {
+                removed.push('\n');
+                removed.push_str(&line);
+            }
+            removed
+        };
+        self.lineidx = start.0;
+        self.lidx = start.1;
+        self.setrow();
+        self.setpos()?;
+        Ok(removed)
     }
 
-    fn grapheme_width_lofs_to_lidx(&self) -> u16 {
-        let st = &self.lines[self.lineidx][self.lofs..self.lidx];
-        if !st.contains('\t') {
-            return string_width(&st) as u16;
+    // Print the line number gutter for lineidx, or a blank one for wrapped rows
+    fn drawgutter(&mut self, lineidx: Option<usize>) -> Result<()> {
+        let width = self.gutter() as usize;
+        if width == 0 {
+            return Ok(());
+        }
+        let mut style = ContentStyle::new();
+        if lineidx != Some(self.lineidx) {
+            style.foreground_color = Some(Color::DarkGrey);
         }
-        let ofs = string_width(&self.lines[self.lineidx][..self.lofs]) % self.tabstop as usize;
+        let number = match lineidx {
+            Some(lineidx) => format!("{:>1$} ", lineidx + 1, width - 1),
+            None => " ".repeat(width),
+        };
+        self.term
+            .queue(PrintStyledContent(StyledContent::new(style, number)))?;
+        Ok(())
+    }
+
+    // Print lines[lineidx] from byte start to end, clipped to maxwidth columns
+    // Returns true when the line is too wide
+    fn drawline(
+        &mut self,
+        lineidx: usize,
+        start: usize,
+        end: usize,
+        maxwidth: usize,
+    ) -> Result<bool> {
+        let styles = self.line_styles(lineidx);
+        let eol = end == self.lines[lineidx].len();
+        let line = &self.lines[lineidx][start..end];
+        let stwidth = string_width(line);
+
+        if styles.is_empty() && stwidth <= maxwidth && !line.contains('\t') {
+            //Printable
+            self.term.queue(Print(line))?;
+            return Ok(false);
+        }
+
+        // Too Wide, Tabs or Styled
+        // Tab stops count from the start of the line, or of the row when wrapped
+        let ts = self.tabstop as usize;
+        let col0 = if self.soft_wrap {
+            0
+        } else {
+            display_width(&self.lines[lineidx][..start], ts)
+        };
+        let mut width = 0usize;
         let mut char_width;
-        let mut width = 0;
-        for (_, g) in st.grapheme_indices(true) {
+        let mut s = String::with_capacity(200);
+        let mut style = ContentStyle::new();
+        for (i, g) in line.grapheme_indices(true) {
+            let gstyle = grapheme_style(&styles, start + i);
+            if gstyle != style {
+                self.term
+                    .queue(PrintStyledContent(StyledContent::new(style, &s)))?;
+                s.clear();
+                style = gstyle;
+            }
             if g == "\t" {
-                let ts = self.tabstop as usize;
-                char_width = ts - ((width + ofs) % ts);
+                char_width = ts - ((col0 + width) % ts);
+                if width + char_width > maxwidth {
+                    break;
+                }
+                s.push_str(&"→".repeat(char_width));
             } else {
                 char_width = string_width(g);
+                if width + char_width > maxwidth {
+                    break;
+                }
+                s.push_str(g);
             }
             width += char_width;
         }
-        return width as u16;
+        self.term
+            .queue(PrintStyledContent(StyledContent::new(style, &s)))?;
+
+        // Show a selected line ending
+        let eol_style = grapheme_style(&styles, end);
+        if eol && eol_style != ContentStyle::new() && width < maxwidth {
+            self.term
+                .queue(PrintStyledContent(StyledContent::new(eol_style, " ")))?;
+        }
+        Ok(stwidth > maxwidth)
+    }
+
+    fn grapheme_idx_at_idx(&self, idx: usize) -> usize {
+        grapheme_idx_at_idx(&self.lines[self.lineidx], idx)
+    }
+
+    fn grapheme_width_lofs_to_lidx(&self) -> u16 {
+        let line = &self.lines[self.lineidx];
+        let st = &line[self.lofs..self.lidx];
+        if !st.contains('\t') {
+            return string_width(st) as u16;
+        }
+        // Tab stops count from the start of the line
+        let ts = self.tabstop as usize;
+        (display_width(&line[..self.lidx], ts) - display_width(&line[..self.lofs], ts)) as u16
+    }
+
+    // Width of the line number gutter, 0 when off
+    fn gutter(&self) -> u16 {
+        if self.line_numbers {
+            self.lines.len().to_string().len() as u16 + 1
+        } else {
+            0
+        }
     }
 
     pub fn handle_event(&mut self, event: Event) -> Result<Option<EditorEvent>> {
+        let had_selection = self.anchor.is_some();
         match event {
-            // Doesn't work to detect ctrl-shift  <= a *terminal* thing I thinks
-            // Control Keys
-            Event::Key(KeyEvent {
-                code,
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                ..
-            }) => match code {
-                // Move to beginning
-                KeyCode::Char('a') => {
-                    self.lidx = 0;
-                    self.lofs = 0;
-                    self.setpos()?;
-                }
-                // End of text (CTRL-C)
-                KeyCode::Char('c') => {
-                    //if self.should_print_line_on_control_c {
-                    //	self.print(&format!("{}{}", self.prompt, self.editor))?;
-                    //}
-                    return Ok(Some(EditorEvent::CtrlC));
-                }
-                // End of transmission (CTRL-D)
-                KeyCode::Char('d') => {
-                    //writeln!(self.term)?;
-                    return Ok(Some(EditorEvent::CtrlD));
-                }
-                // Move to end
-                KeyCode::Char('e') => {
-                    self.move_end()?;
-                }
-                KeyCode::Char('l') => {
-                    self.printx = 0;
-                    self.printy = 0;
-                    self.redraw()?;
-                }
-                KeyCode::Char('n') => {
-                    return Ok(Some(EditorEvent::CtrlS));
-                }
-                KeyCode::Char('q') => {
-                    return Ok(Some(EditorEvent::CtrlQ));
-                }
-                KeyCode::Char('s') => {
-                    return Ok(Some(EditorEvent::CtrlS));
-                }
-                KeyCode::Char('x') => {
-                    return Ok(Some(EditorEvent::CtrlX));
-                }
-                KeyCode::Char('u') => {
-                    self.lines[self.lineidx].drain(0..self.lidx);
-                    self.redraw()?;
-                }
-                KeyCode::Down => {
-                    self.resize_split(3)?;
-                }
-                KeyCode::End => {
-                    self.lineidx = self.lines.len().saturating_sub(1);
-                    self.scrollstart = self.lineidx; //.saturating_sub(1);
-                    self.cury = self.printlines + 2; // + (self.lineidx - self.scrollstart) as u16;
-                    self.lidx = self.len();
-                    self.setpos()?;
-                    self.redraw()?;
-                }
-                KeyCode::Home => {
-                    self.lineidx = 0;
-                    self.scrollstart = 0;
-                    self.cury = self.printlines + 2;
-                    self.lidx = 0;
-                    self.setpos()?;
-                    self.redraw()?;
-                }
-                // Ctrl-Left  Move cursor left to previous word
-                KeyCode::Left => {
-                    if self.lidx == 0 {
-                        self.move_up(1, true)?;
-                        self.lidx = self.len();
-                    }
-                    while self.lidx > 0 && self.prev_char(self.lidx).is_whitespace() {
-                        self.lidx = self.prev_grapheme_idx_from_idx(self.lidx);
-                    }
-                    while self.lidx > 0 && !self.prev_char(self.lidx).is_whitespace() {
-                        self.lidx = self.prev_grapheme_idx_from_idx(self.lidx);
-                    }
-                    self.setpos()?;
-                }
-                KeyCode::PageDown => {
-                    // Go forward in history, Quit if you're caught up
-                    // Only pageUp can activate history...  No need to save the index here
-                    self.writehistory(WriteHistoryType::PageDown)?;
-                }
-                KeyCode::PageUp => {
-                    // Go back in history. Activate if necessary
-                    if !self.hb_active {
-                        self.hb_active = true;
-                        self.hb_start_index = self.histbuf.get_last_index();
-                        self.hb_end_index = self.hb_start_index;
-                    } else {
-                        if self.hb_start_index == 0 {
-                            return Ok(None);
-                        }
-                    }
-                    self.writehistory(WriteHistoryType::PageUp)?;
-                }
-                // Ctrl-Right  Move cursor right to next word
-                KeyCode::Right => {
-                    while self.lidx < self.len() && !self.ch(self.lidx).is_whitespace() {
-                        self.lidx = self.next_grapheme_idx_from_idx(self.lidx);
-                    }
-                    if self.lidx == self.len() {
-                        self.move_down(1, true)?;
-                        self.lidx = 0;
-                    }
-                    while self.lidx < self.len() && self.ch(self.lidx).is_whitespace() {
-                        self.lidx = self.next_grapheme_idx_from_idx(self.lidx);
-                    }
-                    self.setpos()?;
-                }
-                KeyCode::Up => {
-                    self.resize_split(-3)?;
-                }
-                _ => {}
-            },
-            /////////////////////////////////////////////////////////////////////////////
-            // Everything Else
-            Event::Key(KeyEvent {
-                code,
-                modifiers: _,
-                kind: KeyEventKind::Press,
-                ..
-            }) => match code {
-                KeyCode::Backspace => {
-                    if self.lidx == 0 {
-                        if self.lineidx == 0 {
-                            return Ok(None);
-                        }
-                        self.lidx = self.lines[self.lineidx - 1].len();
-                        //self.loose_cursor = true;
-                        let s = self.lines[self.lineidx].clone();
-                        self.lines[self.lineidx - 1].push_str(&s);
-                        self.lines.remove(self.lineidx);
-                        self.move_up(1, false)?;
-                        self.setpos()?;
-                        self.redraw()?;
-                    } else {
-                        if self.lidx > self.len() {
-                            self.lidx = self.len();
-                            self.lofs = 0;
-                        }
-                        let start = self.prev_grapheme_idx_from_idx(self.lidx);
-                        let mut gwid = self.grapheme_width_lofs_to_lidx(); // width with tabs computed the correct width
-                        self.lines[self.lineidx].replace_range(start..self.lidx, "");
-                        self.lidx = start;
-                        gwid = gwid.saturating_sub(self.grapheme_width_lofs_to_lidx());
-                        self.curx = self.curx.saturating_sub(gwid);
-                        self.redrawline()?;
-                    }
-                }
-                KeyCode::Char(c) => {
-                    self.insert_charstr(&c.to_string())?;
-                }
-                KeyCode::Delete => {
-                    if self.lidx == self.len() {
-                        if self.lineidx + 1 < self.lines.len() {
-                            let s = self.lines[self.lineidx + 1].clone();
-                            self.lines[self.lineidx].push_str(&s);
-                            self.lines.remove(self.lineidx + 1);
-                            self.redraw()?;
-                        }
-                    } else {
-                        let end = self.next_grapheme_idx_from_idx(self.lidx);
-                        self.lines[self.lineidx].replace_range(self.lidx..end, "");
-                        self.redrawline()?;
-                    }
-                }
-                KeyCode::Down => {
-                    self.move_down(1, false)?;
-                    self.redraw()?;
-                }
-                KeyCode::End => {
-                    self.move_end()?;
-                }
-                KeyCode::Esc => {
-                    self.writehistory(WriteHistoryType::Quit)?;
-                    self.hb_active = false;
-                }
-                KeyCode::Enter => {
-                    if self.lidx > self.len() {
-                        self.lidx = self.len();
-                    }
-                    self.lines.insert(
-                        self.lineidx + 1,
-                        self.lines[self.lineidx][self.lidx..].to_string(),
-                    );
-                    self.lines[self.lineidx].drain(self.lidx..);
-                    self.move_down(1, true)?;
+            Event::Key(
+                key @ KeyEvent {
+                    code,
+                    modifiers,
+                    kind: KeyEventKind::Press,
+                    ..
+                },
+            ) => {
+                self.last_cmd = std::mem::replace(&mut self.this_cmd, CmdKind::Other);
+                if self.invalid.take().is_some() {
                     self.redraw()?;
                 }
-                KeyCode::Home => {
-                    self.lidx = 0;
-                    self.lofs = 0;
-                    self.setpos()?;
+
+                // Searching, replacing, completing or vi mode take the keys first
+                if self.search_key(code, modifiers)?
+                    || self.replace_key(code, modifiers)?
+                    || self.completion_key(code, modifiers)?
+                    || self.vi_key(code, modifiers)?
+                {
+                    self.update_hint()?;
+                    self.term.queue(cursor::MoveTo(self.curx, self.cury))?;
+                    self.term.flush()?;
+                    return Ok(None);
                 }
-                KeyCode::Left => {
-                    if self.lidx == 0 {
-                        if self.lineidx == 0 {
-                            return Ok(None);
+
+                if let Some((action, select)) = self.keymap.action(code, modifiers) {
+                    // Shift + motion extends the selection, any other motion drops it
+                    if select {
+                        if self.anchor.is_none() {
+                            self.anchor = Some((self.lineidx, self.lidx));
                         }
-                        self.move_up(1, true)?;
-                        self.lidx = self.len();
-                    } else {
-                        self.lidx = self.prev_grapheme_idx_from_idx(self.lidx);
+                    } else if action.is_motion() {
+                        self.anchor = None;
                     }
-                    self.setpos()?;
-                }
-                KeyCode::PageDown => {
-                    let numlines = self.sizey - self.printlines - 2;
-                    self.move_down(numlines, false)?;
-                }
-                KeyCode::PageUp => {
-                    let numlines = self.sizey - self.printlines - 2;
-                    self.move_up(numlines, false)?;
-                }
-                KeyCode::Right => {
-                    if self.lidx >= self.len() {
-                        if self.lineidx + 1 == self.lines.len() {
-                            return Ok(None);
-                        }
-                        self.move_down(1, true)?;
-                        self.lidx = 0;
-                    } else {
-                        self.lidx = self.next_grapheme_idx_from_idx(self.lidx);
+                    if let Some(event) = self.run_action(action, key)? {
+                        self.update_hint()?;
+                        return Ok(Some(event));
                     }
-                    self.setpos()?;
+                } else if self.pass_keys {
+                    return Ok(Some(EditorEvent::Key(key)));
                 }
-                KeyCode::Tab => {
-                    self.insert_charstr("\t")?;
-                }
-                KeyCode::Up => {
-                    self.move_up(1, false)?;
+            }
+            // Bracketed Paste - Insert everything at once, and redraw once
+            Event::Paste(text) => {
+                self.last_cmd = std::mem::replace(&mut self.this_cmd, CmdKind::Other);
+                let text = text.replace("\r\n", "\n").replace('\r', "\n");
+                self.completion = None;
+                if self.search.is_some() {
+                    self.search_paste(&text)?;
+                } else if self.replace.is_some() {
+                    self.replace_paste(&text)?;
+                } else {
+                    self.paste(&text)?;
                 }
-                _ => {}
-            },
+            }
             Event::Resize(x, y) => {
-                let curp: f32 = (self.printlines as f32 / self.sizey as f32)
-                    .max(0.9)
-                    .min(0.1);
+                let curp: f32 = (self.printlines as f32 / self.sizey as f32).clamp(0.1, 0.9);
                 let delta: i16 = (curp * y as f32) as i16 - self.printlines as i16;
 
                 self.sizex = x;
@@ -560,6 +826,14 @@ impl Editor {
             }
             _ => {}
         }
+        if self.soft_wrap {
+            // Edits may have moved the cursor to another row
+            self.setpos()?;
+        }
+        if had_selection || self.anchor.is_some() {
+            self.redraw()?;
+        }
+        self.update_hint()?;
         if false {
             // Debug code
             self.split_prompt = string_to_hex(&self.lines[self.lineidx], 40);
@@ -570,38 +844,128 @@ impl Editor {
         Ok(None)
     }
 
+    // A page of print history from index start, up to printlines rows
+    fn history_page(&self, start: usize) -> Vec<u8> {
+        let mut linecnt = self.sizex;
+        let mut num_lines = 0;
+        let mut buf = Vec::<u8>::with_capacity(self.printlines as usize * self.sizex as usize);
+        while let Some(ch) = self.histbuf.get(start + buf.len()) {
+            buf.push(ch);
+            linecnt -= 1;
+            if ch == b'\n' || linecnt == 0 {
+                linecnt = self.sizex - 1;
+                num_lines += 1;
+                if num_lines >= self.printlines {
+                    break;
+                }
+            }
+        }
+        buf
+    }
+
     fn insert_charstr(&mut self, ch: &str) -> Result<()> {
         if self.lidx > self.len() {
             self.lidx = self.len();
             self.lofs = 0;
         }
+        self.save_undo(CmdKind::Insert);
         //let pre_cnt = self.num_graphemes();
         self.lines[self.lineidx].insert_str(self.lidx, ch);
         //if pre_cnt != self.num_graphemes() {
         self.lidx = self.next_grapheme_idx_from_idx(self.lidx);
-        self.curx = self.grapheme_width_lofs_to_lidx();
+        self.curx = self.gutter() + self.grapheme_width_lofs_to_lidx();
+
+        self.redrawline()?;
+        Ok(())
+    }
 
+    // Insert a tab, or spaces up to the next tab stop
+    fn insert_tab(&mut self) -> Result<()> {
+        if !self.expand_tab {
+            return self.insert_charstr("\t");
+        }
+        if self.lidx > self.len() {
+            self.lidx = self.len();
+        }
+        let ts = self.tabstop as usize;
+        let col = display_width(&self.lines[self.lineidx][..self.lidx], ts);
+        self.save_undo(CmdKind::Insert);
+        self.insert_text(&" ".repeat(ts - col % ts))?;
         self.redrawline()?;
         Ok(())
     }
 
+    // Insert text, which may hold many lines, at the cursor
+    // leaving the cursor after it.  The caller is responsible for the redraw
+    fn insert_text(&mut self, text: &str) -> Result<()> {
+        if self.lidx > self.len() {
+            self.lidx = self.len();
+        }
+        let mut new_lines: Vec<String> = text.split('\n').map(|s| s.to_string()).collect();
+        if new_lines.len() == 1 {
+            self.lines[self.lineidx].insert_str(self.lidx, text);
+            self.lidx += text.len();
+        } else {
+            let tail = self.lines[self.lineidx].split_off(self.lidx);
+            self.lines[self.lineidx].push_str(&new_lines[0]);
+            let last = new_lines.len() - 1;
+            self.lidx = new_lines[last].len();
+            new_lines[last].push_str(&tail);
+            self.lines.splice(self.lineidx + 1, new_lines.drain(1..));
+            self.lineidx += last;
+        }
+        self.loose_cursor = false;
+        self.setrow();
+        self.setpos()?;
+        Ok(())
+    }
+
+    /// The key bindings, to bind or unbind keys
+    pub fn keymap(&mut self) -> &mut Keymap {
+        &mut self.keymap
+    }
+
     fn len(&mut self) -> usize {
         self.lines[self.lineidx].len()
     }
 
+    // Styled byte ranges of a line, drawn by drawline
+    fn line_styles(&self, lineidx: usize) -> Vec<(Range<usize>, ContentStyle)> {
+        let mut styles = self.highlight_styles(lineidx);
+        styles.extend(self.invalid_styles(lineidx));
+        if let Some(range) = self.selection_on_line(lineidx) {
+            let mut style = ContentStyle::new();
+            style.attributes.set(Attribute::Reverse);
+            styles.push((range, style));
+        }
+        styles.extend(self.search_styles(lineidx));
+        styles
+    }
+
     /// Match the curx position as much as possible moving from line to line
     fn matchpos(&mut self) -> Result<()> {
-        // Future work -
-        // It's nice for the cursor to stay at nearly the same curx as you move
-        // up and down... but it's a feature that can wait.
+        if self.this_cmd == CmdKind::Vertical {
+            self.lidx = idx_at_col(
+                &self.lines[self.lineidx],
+                self.goal_col,
+                self.tabstop as usize,
+            );
+        }
         self.setpos()?;
         Ok(())
     }
 
+    // Columns for the text, right of the gutter and left of the > marker
+    fn maxwidth(&self) -> usize {
+        (self.sizex - 1).saturating_sub(self.gutter()) as usize
+    }
+
     fn move_down(&mut self, num: u16, move_to_beginning: bool) -> Result<()> {
+        if self.soft_wrap {
+            return self.move_down_wrapped(num, move_to_beginning);
+        }
         self.loose_cursor = true;
-        if self.lineidx + 1 == self.lines.len() && self.scrollstart + 1 == self.lines.len() as usize
-        {
+        if self.lineidx + 1 == self.lines.len() && self.scrollstart + 1 == self.lines.len() {
             self.lidx = self.len();
             self.setpos()?;
             self.redrawline()?;
@@ -614,14 +978,13 @@ impl Editor {
         // screen.
         // The switch occurs when the real bottom line rises above the bottom
         // of the screen, the virtual bottom line numbers.
-        let virtidx;
-        if self.scrollstart
+        let virtidx = if self.scrollstart
             <= (self.sizey as usize + 2).saturating_sub((self.cury + self.printlines) as usize)
         {
-            virtidx = (self.cury - self.printlines) as usize - 2;
+            (self.cury - self.printlines) as usize - 2
         } else {
-            virtidx = self.scrollstart + (self.sizey - self.printlines) as usize - 3;
-        }
+            self.scrollstart + (self.sizey - self.printlines) as usize - 3
+        };
 
         self.cury += num;
         self.lineidx = (self.lineidx + num as usize).min(self.lines.len().saturating_sub(1));
@@ -644,7 +1007,7 @@ impl Editor {
         if move_to_beginning {
             self.lidx = 0;
             self.lofs = 0;
-            self.curx = 0;
+            self.curx = self.gutter();
         } else {
             self.matchpos()?;
         }
@@ -660,12 +1023,15 @@ impl Editor {
     }
 
     fn move_up(&mut self, num: u16, move_to_end: bool) -> Result<()> {
+        if self.soft_wrap {
+            return self.move_up_wrapped(num, move_to_end);
+        }
         self.loose_cursor = true;
 
         if self.lineidx == 0 && self.scrollstart == 0 {
             self.lofs = 0;
             self.lidx = 0;
-            self.curx = 0;
+            self.curx = self.gutter();
             self.redrawline()?;
             return Ok(());
         }
@@ -683,25 +1049,10 @@ impl Editor {
         Ok(())
     }
 
-    fn next_grapheme_from_idx(&self, idx: usize) -> &str {
-        next_grapheme_from_idx(&self.lines[self.lineidx], idx)
-    }
-
     fn next_grapheme_idx_from_idx(&self, idx: usize) -> usize {
         next_grapheme_idx_from_idx(&self.lines[self.lineidx], idx)
     }
 
-    fn prev_char(&self, idx: usize) -> char {
-        self.prev_grapheme_from_idx(idx)
-            .chars()
-            .next()
-            .unwrap_or('\0')
-    }
-
-    fn prev_grapheme_from_idx(&self, idx: usize) -> &str {
-        prev_grapheme_from_idx(&self.lines[self.lineidx], idx)
-    }
-
     fn prev_grapheme_idx_from_idx(&self, idx: usize) -> usize {
         prev_grapheme_idx_from_idx(&self.lines[self.lineidx], idx)
     }
@@ -736,26 +1087,39 @@ impl Editor {
                 self.split_prompt,
                 self.hb_start_index,
                 self.hb_end_index);*/
-        let s = format!(
-            "=====  AsyncEditor  ========== {} ==  Ctrl ⬅️  / ⮕ / ⬆️/ / ⬇️  ==  Ctrl-PgUp/Ctrl-PgDn  ",
-            self.split_prompt
-        );
+        let s = match (&self.search, &self.replace, &self.invalid) {
+            (Some(search), _, _) => search.prompt(),
+            (None, Some(replace), _) => replace.prompt(),
+            (None, None, Some(invalid)) => invalid.prompt(),
+            (None, None, None) => format!(
+                "=====  AsyncEditor  {}========== {} ==  Ctrl ⬅️  / ⮕ / ⬆️/ / ⬇️  ==  Ctrl-PgUp/Ctrl-PgDn  ",
+                self.vi
+                    .as_ref()
+                    .map_or(String::new(), |vi| format!("==  {}  ", vi.indicator())),
+                self.split_prompt
+            ),
+        };
         let extend_count = (self.sizex as usize).saturating_sub(string_width(&s));
+        let rows = self.sizey.saturating_sub(self.printlines + 2) as usize;
+        self.highlight_update((self.scrollstart + rows).max(self.lineidx + 1));
 
-        self.term
-            .queue(cursor::MoveTo(0, self.printlines + 1 as u16))?;
+        self.term.queue(cursor::MoveTo(0, self.printlines + 1))?;
         self.term
             .queue(terminal::Clear(terminal::ClearType::FromCursorDown))?;
         //tbuf.push_str(&format!("{}{}\n", s, std::iter::repeat("=").take(extend_count).collect::<String>()));
-        self.term.queue(Print(&format!(
-            "{}{}\n",
-            s,
-            std::iter::repeat("=")
-                .take(extend_count)
-                .collect::<String>()
-        )))?;
+        self.term
+            .queue(Print(&format!("{}{}\n", s, "=".repeat(extend_count))))?;
         self.term.queue(cursor::MoveToColumn(0))?;
 
+        if self.soft_wrap {
+            self.redraw_wrapped()?;
+            self.draw_hint()?;
+            self.draw_completion()?;
+            self.term.queue(cursor::MoveTo(self.curx, self.cury))?;
+            self.term.flush()?;
+            return Ok(());
+        }
+
         let end_index = (self.scrollstart
             + (self.sizey.saturating_sub(self.printlines + 2) as usize))
             .min(self.lines.len());
@@ -769,57 +1133,32 @@ impl Editor {
                 continue;
             }
 
-            let line = &self.lines[lidx];
-            let maxwidth = self.sizex as usize - 1;
-            let stwidth = string_width(line);
-            let mut width = 0usize;
-            let mut char_width;
-            let mut s = String::with_capacity(200);
-            let mut news = String::with_capacity(200);
-
-            match (stwidth > maxwidth, line.contains('\t')) {
-                (false, false) => {
-                    //Printable"
-                    self.term.queue(Print(&line))?;
-                }
-                (_, _) => {
-                    // Too Wide or Tabs or both
-                    s.clear();
-                    for (_, g) in line.grapheme_indices(true) {
-                        news.clear();
-                        if g == "\t" {
-                            let ts = self.tabstop as usize;
-                            char_width = ts - (width % ts);
-                            let tab_arrow_string: String =
-                                std::iter::repeat("→").take(char_width as usize).collect();
-                            news.push_str(&tab_arrow_string);
-                        } else {
-                            char_width = string_width(g);
-                            news.push_str(g);
-                        }
-                        if width + char_width as usize > maxwidth {
-                            break;
-                        }
-                        s.push_str(&news);
-                        width += char_width;
-                    }
-                    self.term.queue(Print(&s))?;
-                    if stwidth > maxwidth {
-                        self.term.queue(cursor::MoveToColumn(self.sizex - 1))?;
-                        self.term.queue(Print(&'>'))?;
-                    }
-                }
+            let maxwidth = self.maxwidth();
+            self.drawgutter(Some(lidx))?;
+            if self.drawline(lidx, 0, self.lines[lidx].len(), maxwidth)? {
+                self.term.queue(cursor::MoveToColumn(self.sizex - 1))?;
+                self.term.queue(Print(&'>'))?;
             }
             if lidx != end_index - 1 {
                 self.term.queue(cursor::MoveToNextLine(1))?;
             }
         }
+        self.draw_completion()?;
         self.term.queue(cursor::MoveTo(self.curx, self.cury))?;
         self.term.flush()?;
         Ok(())
     }
 
     fn redrawline(&mut self) -> Result<()> {
+        let rows = self.sizey.saturating_sub(self.printlines + 2) as usize;
+        let highlighted = self.highlight_update((self.scrollstart + rows).max(self.lineidx + 1));
+        if !highlighted.is_empty() && highlighted.end > self.lineidx + 1 {
+            // The highlighting changed below
+            return self.redraw();
+        }
+        if self.soft_wrap {
+            return self.redrawline_wrapped();
+        }
         self.term
             .queue(terminal::Clear(terminal::ClearType::CurrentLine))?;
         self.term.queue(cursor::MoveToColumn(0))?;
@@ -831,52 +1170,17 @@ impl Editor {
         };
 
         // Current line may start at lofs
-        let line = &self.lines[self.lineidx][start..];
-        let maxwidth = self.sizex as usize - 2;
-        let stwidth = string_width(line);
-        let mut width = 0usize;
-        let mut char_width;
-        let mut s = String::with_capacity(200);
-        let mut news = String::with_capacity(200);
-
-        match (stwidth > maxwidth, line.contains('\t')) {
-            (false, false) => {
-                //Printable"
-                //tbuf.push_str(line);
-                self.term.queue(Print(&line))?;
-            }
-            (_, _) => {
-                // Too Wide or Tabs or both
-                s.clear();
-                for (_, g) in line.grapheme_indices(true) {
-                    news.clear();
-                    if g == "\t" {
-                        let ts = self.tabstop as usize;
-                        char_width = ts - (width % ts);
-                        let tab_arrow_string: String =
-                            std::iter::repeat("→").take(char_width as usize).collect();
-                        news.push_str(&tab_arrow_string);
-                    } else {
-                        char_width = string_width(g);
-                        news.push_str(g);
-                    }
-                    if width + char_width as usize > maxwidth + 1 {
-                        break;
-                    }
-                    s.push_str(&news);
-                    width += char_width;
-                }
-                //tbuf.push_str(&line[0..end]);
-                self.term.queue(Print(&s))?;
-            }
-        }
+        let maxwidth = self.maxwidth();
+        let end = self.len();
+        self.drawgutter(Some(self.lineidx))?;
+        self.drawline(self.lineidx, start, end, maxwidth)?;
+        self.draw_hint()?;
         self.term.queue(cursor::MoveTo(self.curx, self.cury))?;
         Ok(())
     }
 
     fn resize_split(&mut self, delta: i16) -> Result<()> {
-        self.term
-            .queue(cursor::MoveTo(self.printx, self.printy as u16))?;
+        self.term.queue(cursor::MoveTo(self.printx, self.printy))?;
         self.term
             .queue(terminal::Clear(terminal::ClearType::FromCursorDown))?;
         let pre = self.printlines as i16;
@@ -885,7 +1189,7 @@ impl Editor {
             .min(self.sizey as i16 - 8) as u16;
         let new_delta = self.printlines as i16 - pre;
         self.cury = (self.cury as i16 + new_delta).max(self.printlines as i16 + 2) as u16;
-        while self.cury >= self.sizey {
+        while !self.soft_wrap && self.cury >= self.sizey {
             self.cury -= 1;
             self.lineidx -= 1;
         }
@@ -894,9 +1198,441 @@ impl Editor {
         Ok(())
     }
 
+    // Do what key is bound to
+    fn run_action(&mut self, action: Action, key: KeyEvent) -> Result<Option<EditorEvent>> {
+        match action {
+            Action::Left => {
+                if self.lidx == 0 {
+                    if self.lineidx == 0 {
+                        return Ok(None);
+                    }
+                    self.move_up(1, true)?;
+                    self.lidx = self.len();
+                } else {
+                    self.lidx = self.prev_grapheme_idx_from_idx(self.lidx);
+                }
+                self.setpos()?;
+            }
+            Action::Right => {
+                if self.accept_hint()? {
+                    return Ok(None);
+                }
+                if self.lidx >= self.len() {
+                    if self.lineidx + 1 == self.lines.len() {
+                        return Ok(None);
+                    }
+                    self.move_down(1, true)?;
+                    self.lidx = 0;
+                } else {
+                    self.lidx = self.next_grapheme_idx_from_idx(self.lidx);
+                }
+                self.setpos()?;
+            }
+            Action::Up => {
+                if self.lineidx == 0 && self.anchor.is_none() && self.history_move(true)? {
+                    return Ok(None);
+                }
+                self.set_goal_col();
+                self.move_up(1, false)?;
+            }
+            Action::Down => {
+                if self.lineidx + 1 == self.lines.len()
+                    && self.anchor.is_none()
+                    && self.history_move(false)?
+                {
+                    return Ok(None);
+                }
+                self.set_goal_col();
+                self.move_down(1, false)?;
+                self.redraw()?;
+            }
+            Action::WordLeft => {
+                self.word_left()?;
+            }
+            Action::WordRight => {
+                self.word_right()?;
+            }
+            Action::LineStart => {
+                self.lidx = 0;
+                self.lofs = 0;
+                self.setpos()?;
+            }
+            Action::LineEnd => {
+                if self.accept_hint()? {
+                    return Ok(None);
+                }
+                self.move_end()?;
+            }
+            Action::PageUp => {
+                let numlines = self.sizey - self.printlines - 2;
+                self.set_goal_col();
+                self.move_up(numlines, false)?;
+            }
+            Action::PageDown => {
+                let numlines = self.sizey - self.printlines - 2;
+                self.set_goal_col();
+                self.move_down(numlines, false)?;
+            }
+            Action::TextStart => {
+                self.lineidx = 0;
+                self.scrollstart = 0;
+                self.wraptop = 0;
+                self.cury = self.printlines + 2;
+                self.lidx = 0;
+                self.setpos()?;
+                self.redraw()?;
+            }
+            Action::TextEnd => {
+                self.lineidx = self.lines.len().saturating_sub(1);
+                self.scrollstart = self.lineidx; //.saturating_sub(1);
+                self.wraptop = 0;
+                self.cury = self.printlines + 2; // + (self.lineidx - self.scrollstart) as u16;
+                self.lidx = self.len();
+                self.setpos()?;
+                self.redraw()?;
+            }
+            Action::Backspace => {
+                if self.delete_selection()? {
+                    self.redraw()?;
+                } else if self.lidx == 0 {
+                    if self.lineidx == 0 {
+                        return Ok(None);
+                    }
+                    self.save_undo(CmdKind::Delete);
+                    self.lidx = self.lines[self.lineidx - 1].len();
+                    //self.loose_cursor = true;
+                    let s = self.lines.remove(self.lineidx);
+                    self.lines[self.lineidx - 1].push_str(&s);
+                    self.move_up(1, false)?;
+                    self.setpos()?;
+                    self.redraw()?;
+                } else {
+                    if self.lidx > self.len() {
+                        self.lidx = self.len();
+                        self.lofs = 0;
+                    }
+                    self.save_undo(CmdKind::Delete);
+                    let start = self.prev_grapheme_idx_from_idx(self.lidx);
+                    let mut gwid = self.grapheme_width_lofs_to_lidx(); // width with tabs computed the correct width
+                    self.lines[self.lineidx].replace_range(start..self.lidx, "");
+                    self.lidx = start;
+                    gwid = gwid.saturating_sub(self.grapheme_width_lofs_to_lidx());
+                    self.curx = self.curx.saturating_sub(gwid);
+                    self.redrawline()?;
+                }
+            }
+            Action::Delete => {
+                if self.delete_selection()? {
+                    self.redraw()?;
+                } else if self.lidx == self.len() {
+                    if self.lineidx + 1 < self.lines.len() {
+                        self.save_undo(CmdKind::Delete);
+                        let s = self.lines.remove(self.lineidx + 1);
+                        self.lines[self.lineidx].push_str(&s);
+                        // The gutter may have narrowed
+                        self.setpos()?;
+                        self.redraw()?;
+                    }
+                } else {
+                    self.save_undo(CmdKind::Delete);
+                    let end = self.next_grapheme_idx_from_idx(self.lidx);
+                    self.lines[self.lineidx].replace_range(self.lidx..end, "");
+                    self.redrawline()?;
+                }
+            }
+            Action::Newline => {
+                self.delete_selection()?;
+                if self.lidx > self.len() {
+                    self.lidx = self.len();
+                }
+                self.save_undo(CmdKind::Edit);
+                let line = &mut self.lines[self.lineidx];
+                let mut tail = line.split_off(self.lidx);
+                let indent = if self.auto_indent {
+                    line[..line.len() - line.trim_start_matches([' ', '\t']).len()].to_string()
+                } else {
+                    String::new()
+                };
+                tail.insert_str(0, &indent);
+                self.lines.insert(self.lineidx + 1, tail);
+                self.move_down(1, true)?;
+                if !indent.is_empty() {
+                    self.lidx = indent.len();
+                    self.setpos()?;
+                }
+                self.redraw()?;
+            }
+            Action::Tab => {
+                self.delete_selection()?;
+                self.insert_tab()?;
+            }
+            Action::Complete => {
+                if !self.complete()? {
+                    self.delete_selection()?;
+                    self.insert_tab()?;
+                }
+            }
+            Action::KillLine => {
+                self.kill_line()?;
+            }
+            Action::KillLineStart => {
+                self.kill_line_start()?;
+            }
+            Action::KillPrevWord => {
+                self.kill_prev_word()?;
+            }
+            Action::KillWord => {
+                self.kill_word()?;
+            }
+            Action::KillWordBack => {
+                self.kill_word_back()?;
+            }
+            Action::Yank => {
+                self.yank()?;
+            }
+            Action::YankPop => {
+                self.yank_pop()?;
+            }
+            Action::Undo => {
+                self.undo()?;
+            }
+            Action::Redo => {
+                self.redo()?;
+            }
+            Action::TransposeChars => {
+                self.transpose_chars()?;
+            }
+            Action::TransposeWords => {
+                self.transpose_words()?;
+            }
+            Action::UpcaseWord => {
+                self.case_word(Case::Upper)?;
+            }
+            Action::DowncaseWord => {
+                self.case_word(Case::Lower)?;
+            }
+            Action::CapitalizeWord => {
+                self.case_word(Case::Capital)?;
+            }
+            Action::Copy => {
+                //if self.should_print_line_on_control_c {
+                //	self.print(&format!("{}{}", self.prompt, self.editor))?;
+                //}
+                if self.selection_range().is_none() {
+                    return Ok(Some(EditorEvent::CtrlC));
+                }
+                self.copy();
+            }
+            Action::Cut => {
+                if self.selection_range().is_none() {
+                    return Ok(Some(EditorEvent::CtrlX));
+                }
+                self.cut()?;
+            }
+            Action::Paste => {
+                let text = self.clipboard.clone();
+                self.paste(&text)?;
+            }
+            Action::SearchForward => {
+                self.search_next(false)?;
+            }
+            Action::SearchBackward => {
+                self.search_next(true)?;
+            }
+            Action::Replace => {
+                self.replace_start()?;
+            }
+            Action::ClearScreen => {
+                self.printx = 0;
+                self.printy = 0;
+                self.redraw()?;
+            }
+            Action::SplitUp => {
+                self.resize_split(-3)?;
+            }
+            Action::SplitDown => {
+                self.resize_split(3)?;
+            }
+            Action::HistoryPageUp => {
+                // Go back in history. Activate if necessary
+                if !self.hb_active {
+                    self.hb_active = true;
+                    self.hb_start_index = self.histbuf.get_last_index();
+                    self.hb_end_index = self.hb_start_index;
+                } else {
+                    if self.hb_start_index == 0 {
+                        return Ok(None);
+                    }
+                }
+                self.writehistory(WriteHistoryType::PageUp)?;
+            }
+            Action::HistorySearch => {
+                self.history_search()?;
+            }
+            Action::HistoryPrev => {
+                self.anchor = None;
+                self.history_move(true)?;
+            }
+            Action::HistoryNext => {
+                self.anchor = None;
+                if !self.history.is_browsing() {
+                    return Ok(Some(EditorEvent::CtrlN));
+                }
+                self.history_move(false)?;
+            }
+            Action::HistoryPageDown => {
+                // Go forward in history, Quit if you're caught up
+                // Only pageUp can activate history...  No need to save the index here
+                self.writehistory(WriteHistoryType::PageDown)?;
+            }
+            Action::Escape => {
+                self.anchor = None;
+                self.writehistory(WriteHistoryType::Quit)?;
+                self.hb_active = false;
+            }
+            Action::Insert(text) => {
+                self.delete_selection()?;
+                if text.graphemes(true).count() == 1 {
+                    self.insert_charstr(&text)?;
+                } else {
+                    self.save_undo(CmdKind::Insert);
+                    self.insert_text(&text)?;
+                    self.redraw()?;
+                }
+            }
+            Action::Emit(event) => return Ok(Some(event)),
+            Action::Custom(name) => return Ok(Some(EditorEvent::Custom(name))),
+            Action::Key => return Ok(Some(EditorEvent::Key(key))),
+            Action::Submit => {
+                // A backslash at the end of the line continues on a new line
+                let line = &self.lines[self.lineidx];
+                if self.anchor.is_none() && self.lidx >= line.len() && line.ends_with('\\') {
+                    self.save_undo(CmdKind::Edit);
+                    self.lines[self.lineidx].pop();
+                    self.lidx = self.len();
+                    return self.run_action(Action::Newline, key);
+                }
+                match self.validate()? {
+                    Validation::Valid => {}
+                    Validation::Incomplete => return self.run_action(Action::Newline, key),
+                    Validation::Invalid { .. } => return Ok(None),
+                }
+                let text = self.lines.text().to_string();
+                self.history.submit(text.clone());
+                self.save_undo(CmdKind::Edit);
+                self.set_text("", (0, 0))?;
+                return Ok(Some(EditorEvent::Submit(text)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Start new lines with the leading whitespace of the line above
+    pub fn set_auto_indent(&mut self, auto_indent: bool) {
+        self.auto_indent = auto_indent;
+    }
+
+    /// Complete with completer on Tab
+    pub fn set_completer(&mut self, completer: impl Completer + 'static) {
+        self.completer = Some(Box::new(completer));
+    }
+
+    /// Insert spaces up to the next tab stop instead of a tab
+    pub fn set_expand_tab(&mut self, expand_tab: bool) {
+        self.expand_tab = expand_tab;
+    }
+
+    /// Style the text with highlighter
+    pub fn set_highlighter(&mut self, highlighter: impl Highlighter + 'static) -> Result<()> {
+        self.highlighter = Some(Box::new(highlighter));
+        self.highlights = Highlights::default();
+        self.redraw()
+    }
+
+    /// Show hints from hinter after the cursor at the end of the line
+    pub fn set_hinter(&mut self, hinter: impl Hinter + 'static) {
+        self.hinter = Some(Box::new(hinter));
+    }
+
+    /// Pass on keys without a binding, that would otherwise be ignored, as EditorEvent::Key
+    pub fn set_pass_keys(&mut self, pass_keys: bool) {
+        self.pass_keys = pass_keys;
+    }
+
+    /// Enter sends the text as EditorEvent::Submit and clears the edit area.
+    /// Alt-Enter, Shift-Enter or a backslash at the end of the line start a new line
+    pub fn set_submit_mode(&mut self, submit: bool) {
+        self.keymap.set_submit_mode(submit);
+    }
+
+    // Start moving up or down, keeping the goal column of the first move
+    fn set_goal_col(&mut self) {
+        if self.last_cmd != CmdKind::Vertical {
+            self.goal_col = if self.soft_wrap {
+                (self.curx - self.gutter()) as usize
+            } else {
+                let line = &self.lines[self.lineidx];
+                let lidx = grapheme_idx_at_idx(line, self.lidx);
+                display_width(&line[..lidx], self.tabstop as usize)
+            };
+        }
+        self.this_cmd = CmdKind::Vertical;
+    }
+
+    /// Replace the key bindings
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    /// Show line numbers left of the edit area
+    pub fn set_line_numbers(&mut self, line_numbers: bool) -> Result<()> {
+        self.line_numbers = line_numbers;
+        self.lofs = 0;
+        self.setrow();
+        self.setpos()?;
+        self.redraw()?;
+        Ok(())
+    }
+
+    pub fn set_tabstop(&mut self, tabstop: u8) -> Result<()> {
+        self.tabstop = tabstop.max(1);
+        self.lofs = 0;
+        self.setrow();
+        self.setpos()?;
+        self.redraw()?;
+        Ok(())
+    }
+
+    // Replace the text, with the cursor at pos (clamped to the text)
+    fn set_text(&mut self, text: &str, pos: (usize, usize)) -> Result<()> {
+        self.anchor = None;
+        self.lines = text.split('\n').map(|s| s.to_string()).collect();
+        (self.lineidx, self.lidx) = self.clamp_pos(pos);
+        self.lofs = 0;
+        self.scrollstart = 0;
+        self.wraptop = 0;
+        self.setrow();
+        self.setpos()?;
+        self.redraw()?;
+        Ok(())
+    }
+
+    /// Check the text with validator before it is submitted
+    pub fn set_validator(&mut self, validator: impl Validator + 'static) {
+        self.validator = Some(Box::new(validator));
+    }
+
+    /// Characters besides letters and digits that make up words, "_" by default
+    pub fn set_word_chars(&mut self, word_chars: &str) {
+        self.word_chars = word_chars.to_string();
+    }
+
     // Set self.curx / self.lofs so self.lidx is visable (string_width[lofs to idx] is < maxsize
     fn setpos(&mut self) -> Result<()> {
-        let maxwidth = self.sizex - 1;
+        if self.soft_wrap {
+            return self.setpos_wrapped();
+        }
+        let maxwidth = self.maxwidth() as u16;
         self.lidx = self.grapheme_idx_at_idx(self.lidx);
 
         // loose_cursor - Signals that the cursor just moved off a possibly long line
@@ -922,7 +1658,7 @@ impl Editor {
 
         loop {
             if stwidth <= maxwidth {
-                self.curx = stwidth;
+                self.curx = self.gutter() + stwidth;
                 return Ok(());
             }
             // It would be nice to just subtract the first char width, but for tabs
@@ -932,8 +1668,40 @@ impl Editor {
         }
     }
 
+    // Set self.scrollstart / self.cury so self.lineidx is visable
+    fn setrow(&mut self) {
+        if self.soft_wrap {
+            let starts = self.wrap_rows(self.lineidx);
+            let lidx = grapheme_idx_at_idx(&self.lines[self.lineidx], self.lidx);
+            self.setrow_wrapped(wrap::row_of(&starts, lidx));
+            return;
+        }
+        let rows = self.sizey.saturating_sub(self.printlines + 2).max(1) as usize;
+        if self.lineidx < self.scrollstart {
+            self.scrollstart = self.lineidx;
+        } else if self.lineidx >= self.scrollstart + rows {
+            self.scrollstart = self.lineidx + 1 - rows;
+        }
+        self.cury = (self.lineidx - self.scrollstart) as u16 + self.printlines + 2;
+    }
+
     pub fn text(&self) -> String {
-        self.lines.join("\n")
+        self.lines.text().to_string()
+    }
+
+    // Text between two ordered (lineidx, idx) positions
+    fn text_range(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        if start.0 == end.0 {
+            return self.lines[start.0][start.1..end.1].to_string();
+        }
+        let mut text = self.lines[start.0][start.1..].to_string();
+        for line in self.lines.lines(start.0 + 1..end.0) {
+            text.push('\n');
+            text.push_str(line);
+        }
+        text.push('\n');
+        text.push_str(&self.lines[end.0][..end.1]);
+        text
     }
 
     fn writebuf(&mut self, buf: &[u8]) -> Result<()> {
@@ -942,6 +1710,21 @@ impl Editor {
         // The caller is responsible for restoring the cursor to self.printx/y
         // and clearing the screen below
 
+        self.printbuf(buf)?;
+
+        self.printy = self.printy.min(self.sizey);
+        if self.printy > self.printlines {
+            self.term
+                .queue(terminal::ScrollUp(self.printy - self.printlines))?;
+            self.printy = self.printlines;
+        }
+
+        self.redraw()?;
+        Ok(())
+    }
+
+    // Print buf, moving self.printx/y along, for writebuf to finish off
+    fn printbuf(&mut self, buf: &[u8]) -> Result<()> {
         for line in buf.split_inclusive(|b| *b == b'\n') {
             self.term.write_all(line)?;
             self.term.flush()?;
@@ -958,15 +1741,6 @@ impl Editor {
                 self.printx = x as u16;
             }
         }
-
-        self.printy = self.printy.min(self.sizey);
-        if self.printy > self.printlines {
-            self.term
-                .queue(terminal::ScrollUp(self.printy - self.printlines))?;
-            self.printy = self.printlines;
-        }
-
-        self.redraw()?;
         Ok(())
     }
 
@@ -1028,21 +1802,7 @@ impl Editor {
                 }
                 if self.hb_start_index == 0 {
                     // Load a full page
-                    let mut linecnt = self.sizex;
-                    let mut num_lines = 0;
-                    revbuf =
-                        Vec::<u8>::with_capacity(self.printlines as usize * self.sizex as usize);
-                    while let Some(ch) = self.histbuf.get(self.hb_start_index + revbuf.len()) {
-                        revbuf.push(ch);
-                        linecnt -= 1;
-                        if ch == b'\n' || linecnt == 0 {
-                            linecnt = self.sizex - 1;
-                            num_lines += 1;
-                            if num_lines >= self.printlines {
-                                break;
-                            }
-                        }
-                    }
+                    revbuf = self.history_page(self.hb_start_index);
                     self.hb_end_index = revbuf.len();
                 } else {
                     revbuf = buf.into_iter().rev().collect();
@@ -1064,8 +1824,7 @@ impl Editor {
         // Can't request position: let (cx, cy) = position()?; // Causes Timeout:
         self.histbuf.add(buf);
         if !self.hb_active {
-            self.term
-                .queue(cursor::MoveTo(self.printx, self.printy as u16))?;
+            self.term.queue(cursor::MoveTo(self.printx, self.printy))?;
             self.term
                 .queue(terminal::Clear(terminal::ClearType::FromCursorDown))?;
             self.writebuf(buf)?;
@@ -1077,6 +1836,7 @@ impl Editor {
 impl Drop for Editor {
     fn drop(&mut self) {
         let _ = disable_raw_mode();
+        self.term.queue(DisableBracketedPaste).unwrap();
         self.term.queue(cursor::MoveTo(0, self.sizey - 1)).unwrap();
         self.term.queue(cursor::MoveToNextLine(1)).unwrap();
         self.term.flush().unwrap();
@@ -1101,10 +1861,7 @@ impl io::Write for SharedStdout {
                 }
                 Err(TrySendError::Full(_)) => return Err(io::ErrorKind::WouldBlock.into()),
                 _ => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        "io Error: ThingBuf Receiver Closed",
-                    ));
+                    return Err(io::Error::other("io Error: ThingBuf Receiver Closed"));
                 }
             }
         }
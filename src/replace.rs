@@ -0,0 +1,407 @@
+// Find and Replace in the edit area
+//
+// Alt-% asks for the text to find, then for its replacement, in the split
+// bar.  Alt-R toggles regex mode while typing either.  Each match from the
+// cursor on is then selected, and the split bar asks what to do with it:
+//
+// - y or Space: Replace it and go on
+// - n or Backspace: Skip it
+// - !: Replace it and all the ones after it
+// - .: Replace it and stop
+// - q, Enter or Esc: Stop
+//
+// In regex mode $1 or ${name} in the replacement expand to capture groups.
+// Matches may span lines (\n), and all the replacements are undone as one
+// step.
+
+use crate::{CmdKind, Editor, Result};
+use crossterm::event::{KeyCode, KeyModifiers};
+use regex::{Captures, Regex, RegexBuilder};
+
+#[derive(PartialEq)]
+enum Step {
+    Pattern,     // Typing the text to find
+    Replacement, // Typing the replacement
+    Confirm,     // Asking about each match
+}
+
+pub(crate) struct Replace {
+    pattern: String,
+    replacement: String,
+    regex: bool,       // Alt-R: The pattern is a regex, $1 in the replacement expands
+    re: Option<Regex>, // Compiled pattern, when confirming
+    step: Step,
+    offset: usize, // Where to look for the next match in the text
+    count: usize,  // Replacements made
+    saved: bool,   // The undo step has been saved
+}
+
+// Compile the pattern, matching ^ and $ at each line
+fn compile(pattern: &str, regex: bool) -> Result<Regex> {
+    let pattern = if regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+    Ok(RegexBuilder::new(&pattern).multi_line(true).build()?)
+}
+
+// The replacement for one match
+fn expand(caps: &Captures, replacement: &str, regex: bool) -> String {
+    let mut new = String::new();
+    if regex {
+        caps.expand(replacement, &mut new);
+    } else {
+        new.push_str(replacement);
+    }
+    new
+}
+
+// Where to look after a match ending at end, past it when it was empty
+fn next_offset(text: &str, start: usize, end: usize) -> usize {
+    if start < end {
+        end
+    } else {
+        end + text[end..].chars().next().map_or(1, char::len_utf8)
+    }
+}
+
+// Byte offset in text of (lineidx, idx)
+fn offset_at(text: &str, pos: (usize, usize)) -> usize {
+    text.split('\n')
+        .take(pos.0)
+        .map(|line| line.len() + 1)
+        .sum::<usize>()
+        + pos.1
+}
+
+// (lineidx, idx) of byte offset in text
+fn pos_at(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    match before.rfind('\n') {
+        Some(nl) => (before.matches('\n').count(), offset - nl - 1),
+        None => (0, offset),
+    }
+}
+
+impl Replace {
+    /// Split bar text while replacing
+    pub(crate) fn prompt(&self) -> String {
+        let regex = if self.regex { "Regex " } else { "" };
+        match self.step {
+            Step::Pattern => format!("=====  {regex}Replace: {}  ", self.pattern),
+            Step::Replacement => format!(
+                "=====  {regex}Replace: {}  with: {}  ",
+                self.pattern, self.replacement
+            ),
+            Step::Confirm => format!(
+                "=====  Replace with {}?  (y/n/!/./q)  {} replaced  ",
+                self.replacement, self.count
+            ),
+        }
+    }
+}
+
+impl Editor {
+    /// Replace every match of pattern with replacement, as one undo step
+    ///
+    /// With regex, $1 or ${name} in the replacement expand to capture groups.
+    /// Returns the number of replacements.
+    pub fn replace_all(&mut self, pattern: &str, replacement: &str, regex: bool) -> Result<usize> {
+        let re = compile(pattern, regex)?;
+        self.replace_from(&re, replacement, regex, 0, true)
+    }
+
+    // Replace every match from byte offset on, saving an undo step if save
+    fn replace_from(
+        &mut self,
+        re: &Regex,
+        replacement: &str,
+        regex: bool,
+        offset: usize,
+        save: bool,
+    ) -> Result<usize> {
+        let text = self.lines.text();
+        let mut new_text = String::with_capacity(text.len());
+        let mut last = 0;
+        let mut at = offset;
+        let mut count = 0;
+        while at <= text.len() {
+            let Some(caps) = re.captures_at(&text, at) else {
+                break;
+            };
+            let m = caps.get(0).unwrap();
+            new_text.push_str(&text[last..m.start()]);
+            new_text.push_str(&expand(&caps, replacement, regex));
+            last = m.end();
+            at = next_offset(&text, m.start(), m.end());
+            count += 1;
+        }
+        if count == 0 {
+            return Ok(0);
+        }
+        new_text.push_str(&text[last..]);
+
+        if save {
+            self.save_undo(CmdKind::Edit);
+        }
+        self.anchor = None;
+        self.lines = new_text.split('\n').map(|s| s.to_string()).collect();
+        (self.lineidx, self.lidx) = self.clamp_pos((self.lineidx, self.lidx));
+        self.setrow();
+        self.setpos()?;
+        self.redraw()?;
+        Ok(count)
+    }
+
+    /// Handle a key while replacing, returns false if the key ended the
+    /// replace and still needs to be handled
+    pub(crate) fn replace_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Result<bool> {
+        let Some(replace) = &mut self.replace else {
+            return Ok(false);
+        };
+        if replace.step == Step::Confirm {
+            match code {
+                KeyCode::Char('y') | KeyCode::Char(' ') => {
+                    self.replace_match()?;
+                    self.replace_next()?;
+                }
+                KeyCode::Char('n') | KeyCode::Backspace | KeyCode::Delete => {
+                    self.replace_skip();
+                    self.replace_next()?;
+                }
+                KeyCode::Char('!') => {
+                    if let Some(re) = replace.re.take() {
+                        let (replacement, regex) = (replace.replacement.clone(), replace.regex);
+                        let (offset, save) = (replace.offset, !replace.saved);
+                        self.replace_from(&re, &replacement, regex, offset, save)?;
+                    }
+                    self.replace_stop()?;
+                }
+                KeyCode::Char('.') => {
+                    self.replace_match()?;
+                    self.replace_stop()?;
+                }
+                KeyCode::Char('q') | KeyCode::Enter | KeyCode::Esc => self.replace_stop()?,
+                _ => {}
+            }
+            return Ok(true);
+        }
+
+        let typing = match replace.step {
+            Step::Pattern => &mut replace.pattern,
+            _ => &mut replace.replacement,
+        };
+        match (code, modifiers) {
+            (KeyCode::Char('g'), KeyModifiers::CONTROL) | (KeyCode::Esc, _) => {
+                self.replace_stop()?;
+            }
+            (KeyCode::Char('r'), KeyModifiers::ALT) => {
+                replace.regex = !replace.regex;
+                self.redraw()?;
+            }
+            (KeyCode::Backspace, _) => {
+                typing.pop();
+                self.redraw()?;
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                typing.push(c);
+                self.redraw()?;
+            }
+            (KeyCode::Enter, _) if replace.step == Step::Pattern => {
+                if replace.pattern.is_empty() {
+                    self.replace_stop()?;
+                } else {
+                    replace.step = Step::Replacement;
+                    self.redraw()?;
+                }
+            }
+            (KeyCode::Enter, _) => match compile(&replace.pattern, replace.regex) {
+                Ok(re) => {
+                    replace.re = Some(re);
+                    replace.step = Step::Confirm;
+                    let start = self.clamp_pos((self.lineidx, self.lidx));
+                    let offset = offset_at(&self.lines.text(), start);
+                    if let Some(replace) = &mut self.replace {
+                        replace.offset = offset;
+                    }
+                    self.replace_next()?;
+                }
+                Err(_) => {
+                    // Back to fix the pattern
+                    replace.step = Step::Pattern;
+                    self.redraw()?;
+                }
+            },
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    // Replace the selected match, from the cursor to the anchor
+    fn replace_match(&mut self) -> Result<()> {
+        let Some(Replace {
+            re: Some(re),
+            replacement,
+            regex,
+            saved,
+            ..
+        }) = &self.replace
+        else {
+            return Ok(());
+        };
+        let text = self.lines.text();
+        let start = offset_at(&text, self.clamp_pos((self.lineidx, self.lidx)));
+        let end = match self.anchor {
+            Some(anchor) => offset_at(&text, self.clamp_pos(anchor)),
+            None => start,
+        };
+        let Some(caps) = re
+            .captures_at(&text, start)
+            .filter(|caps| caps.get(0).is_some_and(|m| m.range() == (start..end)))
+        else {
+            return Ok(());
+        };
+        let m = caps.get(0).unwrap();
+        let new = expand(&caps, replacement, *regex);
+        let (start, end) = (pos_at(&text, m.start()), pos_at(&text, m.end()));
+        let next = next_offset(&text, m.start(), m.end()) - m.len() + new.len();
+
+        if !saved {
+            self.save_undo(CmdKind::Edit);
+        }
+        self.anchor = None;
+        self.delete_range(start, end)?;
+        self.insert_text(&new)?;
+        if let Some(replace) = &mut self.replace {
+            replace.saved = true;
+            replace.count += 1;
+            replace.offset = next;
+        }
+        Ok(())
+    }
+
+    // Select the next match, or finish when there are no more
+    fn replace_next(&mut self) -> Result<()> {
+        let Some(Replace {
+            re: Some(re),
+            offset,
+            ..
+        }) = &self.replace
+        else {
+            return Ok(());
+        };
+        let text = self.lines.text();
+        if *offset > text.len() {
+            // Past an empty match at the end
+            return self.replace_stop();
+        }
+        let Some(m) = re.find_at(&text, *offset) else {
+            return self.replace_stop();
+        };
+        let (lineidx, lidx) = pos_at(&text, m.start());
+        self.anchor = Some(pos_at(&text, m.end()));
+        self.lineidx = lineidx;
+        self.lidx = lidx;
+        self.loose_cursor = true;
+        self.setrow();
+        self.setpos()?;
+        self.redraw()?;
+        Ok(())
+    }
+
+    // Skip the selected match
+    fn replace_skip(&mut self) {
+        let text = self.lines.text();
+        let start = offset_at(&text, self.clamp_pos((self.lineidx, self.lidx)));
+        let end = match self.anchor {
+            Some(anchor) => offset_at(&text, self.clamp_pos(anchor)),
+            None => start,
+        };
+        if let Some(replace) = &mut self.replace {
+            replace.offset = next_offset(&text, start, end);
+        }
+    }
+
+    // Paste into the text being typed
+    pub(crate) fn replace_paste(&mut self, text: &str) -> Result<()> {
+        if let Some(replace) = &mut self.replace {
+            let line = text.lines().next().unwrap_or("");
+            match replace.step {
+                Step::Pattern => replace.pattern.push_str(line),
+                Step::Replacement => replace.replacement.push_str(line),
+                Step::Confirm => return Ok(()),
+            }
+            self.redraw()?;
+        }
+        Ok(())
+    }
+
+    // Alt-%: Start a find and replace at the cursor
+    pub(crate) fn replace_start(&mut self) -> Result<()> {
+        self.anchor = None;
+        self.replace = Some(Replace {
+            pattern: String::new(),
+            replacement: String::new(),
+            regex: false,
+            re: None,
+            step: Step::Pattern,
+            offset: 0,
+            count: 0,
+            saved: false,
+        });
+        self.redraw()?;
+        Ok(())
+    }
+
+    fn replace_stop(&mut self) -> Result<()> {
+        self.replace = None;
+        self.anchor = None;
+        self.redraw()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_offset_steps_past_empty_matches() {
+        let text = "aé\nb";
+        assert_eq!(next_offset(text, 0, 1), 1);
+        assert_eq!(next_offset(text, 1, 1), 3); // Past the two byte é
+        assert_eq!(next_offset(text, 3, 3), 4);
+        // Past the end, where the confirm loop stops
+        assert_eq!(next_offset(text, 5, 5), 6);
+    }
+
+    #[test]
+    fn empty_matches_at_the_end_are_found_once() {
+        let re = compile("$", true).unwrap();
+        let text = "ab\ncd";
+        let mut found = Vec::new();
+        let mut offset = 0;
+        while offset <= text.len() {
+            let Some(m) = re.find_at(text, offset) else {
+                break;
+            };
+            found.push(m.start());
+            offset = next_offset(text, m.start(), m.end());
+        }
+        assert_eq!(found, [2, 5]);
+    }
+
+    #[test]
+    fn pos_at_and_offset_at_agree() {
+        let text = "one\n\ntwo é\n";
+        assert_eq!(pos_at(text, 0), (0, 0));
+        assert_eq!(pos_at(text, 3), (0, 3));
+        assert_eq!(pos_at(text, 4), (1, 0));
+        assert_eq!(pos_at(text, 5), (2, 0));
+        assert_eq!(pos_at(text, 12), (3, 0));
+        for offset in (0..=text.len()).filter(|&n| text.is_char_boundary(n)) {
+            assert_eq!(offset_at(text, pos_at(text, offset)), offset);
+        }
+    }
+}
@@ -0,0 +1,402 @@
+// Rope of Lines, the backing store for the edit buffer
+//
+// The lines are kept in chunks of up to CHUNK_MAX lines, so inserting or
+// removing a line only moves the lines of its own chunk, and a line is
+// found by a binary search over the chunk starts.  Chunks are shared
+// behind Arc, which makes a clone (an undo step) cost one pointer per
+// chunk.  A chunk is copied the first time it is changed after a clone.
+//
+// Lines are plain Strings without the \n, so all the grapheme work on a
+// line still happens on a &str.
+//
+// Deleting lines merges the chunks around the gap while they fit in one,
+// so heavy deletes don't leave many tiny chunks behind.  The whole text,
+// joined with \n, is built on first use and kept until the next change, so
+// submitting, validating and find and replace don't rebuild it every time.
+//
+// The rope also notes which lines changed, for the highlighter to only
+// highlight those again: take_changed() returns the range of lines changed
+// since it was last called.  Lines after the range are the lines that were
+// there before, moved by the number of lines added or removed.  A clone,
+// such as an undo step put back, counts as changed throughout.
+
+use std::{
+    cell::OnceCell,
+    ops::{Index, IndexMut, Range},
+    sync::Arc,
+};
+
+const CHUNK_MAX: usize = 512;
+
+#[derive(Default)]
+pub(crate) struct Rope {
+    changed: Option<Range<usize>>, // Lines changed since take_changed()
+    chunks: Vec<Arc<Vec<String>>>,
+    starts: Vec<usize>, // Index of the first line of each chunk
+    len: usize,
+    text: OnceCell<Arc<str>>, // The lines joined with \n, until changed
+}
+
+impl Rope {
+    /// Remove the lines in range, returning them
+    pub(crate) fn drain(&mut self, range: Range<usize>) -> Vec<String> {
+        let mut removed = Vec::with_capacity(range.len());
+        if range.is_empty() {
+            return removed;
+        }
+        self.change(range.start, range.len(), 0);
+        let (mut c, mut ofs) = self.locate(range.start);
+        let first = c;
+        let mut num = range.len();
+        while num > 0 {
+            let take = (self.chunks[c].len() - ofs).min(num);
+            if take == self.chunks[c].len() {
+                // The whole chunk goes, no need to copy it first
+                let chunk = self.chunks.remove(c);
+                removed.extend(Arc::unwrap_or_clone(chunk));
+            } else {
+                removed.extend(Arc::make_mut(&mut self.chunks[c]).drain(ofs..ofs + take));
+                c += 1;
+            }
+            num -= take;
+            ofs = 0;
+        }
+        self.len -= removed.len();
+        let first = self.merge(first);
+        self.reindex(first);
+        removed
+    }
+
+    // Note that removed lines at line at were replaced by inserted lines
+    fn change(&mut self, at: usize, removed: usize, inserted: usize) {
+        self.text.take();
+        let Range { start, end } = self.changed.take().unwrap_or(at..at);
+        let end = if end > at + removed {
+            end - removed + inserted
+        } else {
+            end.min(at)
+        };
+        self.changed = Some(start.min(at)..end.max(at + inserted));
+    }
+
+    pub(crate) fn insert(&mut self, idx: usize, line: String) {
+        self.change(idx, 0, 1);
+        let (c, ofs) = self.locate(idx);
+        if c == self.chunks.len() {
+            self.chunks.push(Arc::new(vec![line]));
+        } else {
+            let chunk = Arc::make_mut(&mut self.chunks[c]);
+            chunk.insert(ofs, line);
+            if chunk.len() > CHUNK_MAX * 2 {
+                let tail = chunk.split_off(CHUNK_MAX);
+                self.chunks.insert(c + 1, Arc::new(tail));
+            }
+        }
+        self.len += 1;
+        self.reindex(c);
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &String> {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The lines in range
+    pub(crate) fn lines(&self, range: Range<usize>) -> impl Iterator<Item = &String> {
+        let (c, ofs) = self.locate(range.start);
+        self.chunks[c..]
+            .iter()
+            .flat_map(|chunk| chunk.iter())
+            .skip(ofs)
+            .take(range.len())
+    }
+
+    // Chunk holding line idx, and the offset of the line in it.  Returns
+    // the end of the last chunk (or chunks.len() if empty) for idx == len.
+    fn locate(&self, idx: usize) -> (usize, usize) {
+        assert!(idx <= self.len, "line {idx} out of range, len {}", self.len);
+        if idx == self.len {
+            return match self.chunks.len() {
+                0 => (0, 0),
+                n => (n - 1, self.chunks[n - 1].len()),
+            };
+        }
+        let c = self.starts.partition_point(|&start| start <= idx) - 1;
+        (c, idx - self.starts[c])
+    }
+
+    // Merge chunk c with the chunks either side of it while they fit in one,
+    // returning the first chunk changed
+    fn merge(&mut self, c: usize) -> usize {
+        let fits = |a: &Vec<String>, b: &Vec<String>| a.len() + b.len() <= CHUNK_MAX;
+        let mut c = c.min(self.chunks.len().saturating_sub(1));
+        if c + 1 < self.chunks.len() && fits(&self.chunks[c], &self.chunks[c + 1]) {
+            let next = Arc::unwrap_or_clone(self.chunks.remove(c + 1));
+            Arc::make_mut(&mut self.chunks[c]).extend(next);
+        }
+        if c > 0 && c < self.chunks.len() && fits(&self.chunks[c - 1], &self.chunks[c]) {
+            let chunk = Arc::unwrap_or_clone(self.chunks.remove(c));
+            Arc::make_mut(&mut self.chunks[c - 1]).extend(chunk);
+            c -= 1;
+        }
+        c
+    }
+
+    // Recompute the chunk starts from chunk c on
+    fn reindex(&mut self, c: usize) {
+        self.starts.truncate(c);
+        let mut start = match c {
+            0 => 0,
+            _ => self.starts[c - 1] + self.chunks[c - 1].len(),
+        };
+        for chunk in &self.chunks[c..] {
+            self.starts.push(start);
+            start += chunk.len();
+        }
+    }
+
+    pub(crate) fn remove(&mut self, idx: usize) -> String {
+        assert!(idx < self.len, "line {idx} out of range, len {}", self.len);
+        self.change(idx, 1, 0);
+        let (c, ofs) = self.locate(idx);
+        let line = Arc::make_mut(&mut self.chunks[c]).remove(ofs);
+        if self.chunks[c].is_empty() {
+            self.chunks.remove(c);
+        }
+        self.len -= 1;
+        let c = self.merge(c);
+        self.reindex(c);
+        line
+    }
+
+    /// Insert many lines before line idx
+    pub(crate) fn splice(&mut self, idx: usize, lines: impl IntoIterator<Item = String>) {
+        let (c, ofs) = self.locate(idx);
+        let len = self.len;
+        let mut new_chunks = Vec::new();
+        let mut tail = Vec::new();
+        if c < self.chunks.len() {
+            let chunk = Arc::make_mut(&mut self.chunks[c]);
+            tail = chunk.split_off(ofs);
+        }
+        let mut chunk = Vec::new();
+        for line in lines {
+            self.len += 1;
+            chunk.push(line);
+            if chunk.len() == CHUNK_MAX {
+                new_chunks.push(Arc::new(std::mem::take(&mut chunk)));
+            }
+        }
+        chunk.append(&mut tail);
+        if !chunk.is_empty() {
+            new_chunks.push(Arc::new(chunk));
+        }
+        let at = if c < self.chunks.len() && self.chunks[c].is_empty() {
+            self.chunks.remove(c);
+            c
+        } else {
+            (c + 1).min(self.chunks.len())
+        };
+        self.chunks.splice(at..at, new_chunks);
+        self.reindex(c.min(at));
+        self.change(idx, 0, self.len - len);
+    }
+
+    /// The lines changed since the last call, if any
+    pub(crate) fn take_changed(&mut self) -> Option<Range<usize>> {
+        self.changed.take()
+    }
+
+    /// The lines joined with \n, shared until the next change
+    pub(crate) fn text(&self) -> Arc<str> {
+        let text = self.text.get_or_init(|| {
+            let size = self.iter().map(|line| line.len() + 1).sum();
+            let mut text = String::with_capacity(size);
+            for (i, line) in self.iter().enumerate() {
+                if i > 0 {
+                    text.push('\n');
+                }
+                text.push_str(line);
+            }
+            text.into()
+        });
+        text.clone()
+    }
+}
+
+impl Clone for Rope {
+    fn clone(&self) -> Self {
+        Self {
+            changed: Some(0..self.len),
+            chunks: self.chunks.clone(),
+            starts: self.starts.clone(),
+            len: self.len,
+            text: self.text.clone(),
+        }
+    }
+}
+
+impl FromIterator<String> for Rope {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Self {
+        let mut rope = Rope::default();
+        rope.splice(0, iter);
+        rope
+    }
+}
+
+impl Index<usize> for Rope {
+    type Output = String;
+
+    fn index(&self, idx: usize) -> &String {
+        let (c, ofs) = self.locate(idx);
+        &self.chunks[c][ofs]
+    }
+}
+
+impl IndexMut<usize> for Rope {
+    fn index_mut(&mut self, idx: usize) -> &mut String {
+        self.change(idx, 1, 1);
+        let (c, ofs) = self.locate(idx);
+        &mut Arc::make_mut(&mut self.chunks[c])[ofs]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(range: Range<usize>) -> Vec<String> {
+        range.map(|n| n.to_string()).collect()
+    }
+
+    // The rope holds the same lines as model, with consistent chunks
+    fn check(rope: &Rope, model: &[String]) {
+        assert_eq!(rope.len(), model.len());
+        assert!(rope.iter().eq(model.iter()));
+        assert_eq!(&*rope.text(), model.join("\n"));
+        let mut start = 0;
+        for (chunk, &chunk_start) in rope.chunks.iter().zip(&rope.starts) {
+            assert!(!chunk.is_empty());
+            assert_eq!(chunk_start, start);
+            start += chunk.len();
+        }
+        assert_eq!(rope.chunks.len(), rope.starts.len());
+        for n in [0, model.len() / 2, model.len().saturating_sub(1)] {
+            if n < model.len() {
+                assert_eq!(rope[n], model[n]);
+            }
+        }
+    }
+
+    #[test]
+    fn edits_match_a_vec() {
+        let mut model = lines(0..2000);
+        let mut rope: Rope = model.iter().cloned().collect();
+        check(&rope, &model);
+
+        // A small deterministic mix of edits across chunk boundaries
+        let mut seed = 12345usize;
+        let mut next = |n: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % n.max(1)
+        };
+        for step in 0..300 {
+            match step % 5 {
+                0 => {
+                    let idx = next(model.len() + 1);
+                    model.insert(idx, format!("i{step}"));
+                    rope.insert(idx, format!("i{step}"));
+                }
+                1 if !model.is_empty() => {
+                    let idx = next(model.len());
+                    assert_eq!(rope.remove(idx), model.remove(idx));
+                }
+                2 => {
+                    let start = next(model.len() + 1);
+                    let end = (start + next(700)).min(model.len());
+                    let removed: Vec<String> = model.drain(start..end).collect();
+                    assert_eq!(rope.drain(start..end), removed);
+                }
+                3 => {
+                    let idx = next(model.len() + 1);
+                    let new = lines(0..next(900));
+                    model.splice(idx..idx, new.iter().cloned());
+                    rope.splice(idx, new);
+                }
+                _ if !model.is_empty() => {
+                    let idx = next(model.len());
+                    model[idx].push('x');
+                    rope[idx].push('x');
+                }
+                _ => {}
+            }
+            check(&rope, &model);
+        }
+    }
+
+    #[test]
+    fn deletes_merge_small_chunks() {
+        let mut rope: Rope = lines(0..CHUNK_MAX * 8).into_iter().collect();
+        for _ in 0..CHUNK_MAX * 7 {
+            rope.remove(CHUNK_MAX / 2);
+        }
+        assert_eq!(rope.len(), CHUNK_MAX);
+        assert!(rope.chunks.len() <= 2, "{} chunks", rope.chunks.len());
+        rope.drain(1..CHUNK_MAX - 1);
+        assert_eq!(rope.chunks.len(), 1);
+        assert_eq!(&*rope.text(), format!("0\n{}", CHUNK_MAX * 8 - 1));
+    }
+
+    #[test]
+    fn text_is_kept_until_changed() {
+        let mut rope: Rope = lines(0..3).into_iter().collect();
+        let text = rope.text();
+        assert_eq!(&*text, "0\n1\n2");
+        assert!(Arc::ptr_eq(&text, &rope.text()));
+        rope[1].push('x');
+        assert_eq!(&*rope.text(), "0\n1x\n2");
+        assert_eq!(&*text, "0\n1\n2");
+    }
+
+    #[test]
+    fn clone_is_not_changed_by_edits() {
+        let mut rope: Rope = lines(0..CHUNK_MAX * 3).into_iter().collect();
+        let undo = rope.clone();
+        rope[0].push('x');
+        rope.drain(10..CHUNK_MAX * 2);
+        rope.insert(5, "new".to_string());
+        check(&undo, &lines(0..CHUNK_MAX * 3));
+    }
+
+    #[test]
+    fn take_changed_covers_the_edits() {
+        let mut rope: Rope = lines(0..100).into_iter().collect();
+        assert_eq!(rope.take_changed(), Some(0..100));
+        assert_eq!(rope.take_changed(), None);
+
+        rope[5].push('x');
+        assert_eq!(rope.take_changed(), Some(5..6));
+
+        // Lines after the range are the old ones, moved
+        rope.insert(10, "new".to_string());
+        rope[20].push('x');
+        assert_eq!(rope.take_changed(), Some(10..21));
+
+        rope.drain(30..40);
+        assert_eq!(rope.take_changed(), Some(30..30));
+
+        rope.insert(50, "new".to_string());
+        rope.remove(51);
+        assert_eq!(rope.take_changed(), Some(50..51));
+
+        rope.splice(0, lines(0..3));
+        assert_eq!(rope.take_changed(), Some(0..3));
+
+        assert_eq!(rope.clone().take_changed(), Some(0..rope.len()));
+    }
+}
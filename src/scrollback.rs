@@ -0,0 +1,136 @@
+// Print History Search
+//
+// Ctrl-F / Ctrl-B while the print history is frozen (Ctrl-PgUp) search the
+// history, newer / older, for the query typed in the split bar.  The frozen
+// page jumps to the match, with a few lines before it, and the match is
+// shown in reverse video.  Ctrl-PgUp / Ctrl-PgDn then page on from there.
+//
+// Matching runs over the bytes as they were printed, escape sequences and
+// all, so a match may be missed where color codes split the text.  The
+// history is a ring buffer, so a search copies it out once, and again only
+// when more has been printed since.  The reverse video is written around the
+// match as the page is printed, leaving the bytes counted for the print
+// cursor as they were.
+
+use crate::{Editor, Result, search::Target};
+use crossterm::{QueueableCommand, cursor, terminal};
+use std::{io::Write, ops::Range};
+
+const HIGHLIGHT_ON: &[u8] = b"\x1b[7m";
+const HIGHLIGHT_OFF: &[u8] = b"\x1b[27m";
+
+impl Editor {
+    // Find the next match in the print history and show it
+    pub(crate) fn scrollback_find(&mut self, inclusive: bool, restart: bool) -> Result<()> {
+        let Some(search) = &mut self.search else {
+            return Ok(());
+        };
+        let Target::Scrollback { copy, .. } = &mut search.target else {
+            return Ok(());
+        };
+        // Matches are found in a copy of the history, indices relative to first
+        let first = self.histbuf.get_index();
+        let len = self.histbuf.get_len();
+        if copy.0 != first || copy.1.len() != len {
+            *copy = (first, self.histbuf.get_vec(first, len));
+        }
+
+        let Some(search) = &self.search else {
+            return Ok(());
+        };
+        let Target::Scrollback {
+            origin,
+            found,
+            copy: (_, hist),
+        } = &search.target
+        else {
+            return Ok(());
+        };
+        let origin = *origin;
+        let empty = search.is_empty();
+        let from = match found {
+            Some(m) if !restart => m.start,
+            _ if search.backward => origin.1,
+            _ => origin.0,
+        };
+        let matches = search.find_bytes(hist);
+        let found = if search.backward {
+            let before = |m: &&Range<usize>| {
+                m.start + first < from || (inclusive && m.start + first == from)
+            };
+            matches.iter().rev().find(before).or(matches.last())
+        } else {
+            let after = |m: &&Range<usize>| {
+                m.start + first > from || (inclusive && m.start + first == from)
+            };
+            matches.iter().find(after).or(matches.first())
+        }
+        .map(|m| m.start + first..m.end + first);
+
+        if let Some(search) = &mut self.search {
+            search.failing = found.is_none() && !empty;
+            if let (Target::Scrollback { found: shown, .. }, Some(m)) = (&mut search.target, &found)
+            {
+                *shown = Some(m.clone());
+            }
+        }
+        match found {
+            Some(m) => self.scrollback_show(m),
+            None if empty => self.scrollback_page(origin.0, None),
+            None => self.redraw(),
+        }
+    }
+
+    /// Show the history page starting at start, frozen, highlighting found
+    pub(crate) fn scrollback_page(
+        &mut self,
+        start: usize,
+        found: Option<Range<usize>>,
+    ) -> Result<()> {
+        self.term.queue(cursor::MoveTo(0, 0))?;
+        self.term.queue(terminal::Clear(terminal::ClearType::All))?;
+        self.printx = 0;
+        self.printy = 0;
+
+        let buf = self.history_page(start);
+        self.hb_active = true;
+        self.hb_start_index = start;
+        self.hb_end_index = start + buf.len();
+        if let Some(m) = found {
+            let end = m.end.min(self.hb_end_index).max(start) - start;
+            let begin = m.start.saturating_sub(start).min(end);
+            self.printbuf(&buf[..begin])?;
+            self.term.write_all(HIGHLIGHT_ON)?;
+            self.printbuf(&buf[begin..end])?;
+            self.term.write_all(HIGHLIGHT_OFF)?;
+            return self.writebuf(&buf[end..]);
+        }
+        self.writebuf(&buf)
+    }
+
+    // Show the page holding the match, starting a few lines before it
+    fn scrollback_show(&mut self, found: Range<usize>) -> Result<()> {
+        let first = self.histbuf.get_index();
+        let line_start = |mut idx: usize| {
+            while idx > first && self.histbuf.get(idx - 1) != Some(b'\n') {
+                idx -= 1;
+            }
+            idx
+        };
+        let mut start = line_start(found.start);
+        // Context lines, as long as they don't push the match off the page
+        let context = self.printlines as usize / 3;
+        let limit = start.saturating_sub(context * self.sizex as usize);
+        for _ in 0..context {
+            if start == first {
+                break;
+            }
+            let prev = line_start(start - 1);
+            if prev < limit {
+                break;
+            }
+            start = prev;
+        }
+        self.scrollback_page(start, Some(found))
+    }
+}
@@ -0,0 +1,330 @@
+// Incremental Search in the edit area
+//
+// Ctrl-F / Ctrl-B start a forward / backward search.  While searching,
+// typing extends the query and the cursor jumps to the nearest match,
+// Ctrl-F / Ctrl-B (or Ctrl-R) step to the next / previous match (wrapping
+// around the text), and the query is shown in the split bar.  Alt-R switches
+// between a literal and a regex query.  Enter keeps the cursor at the match, Esc or
+// Ctrl-G returns it to where the search started.  Any other key ends the
+// search at the match and is then handled as usual.
+//
+// Ctrl-R searches the input history backward instead, showing the matching
+// entry in the edit area, see history.rs.  While the print history is
+// frozen (Ctrl-PgUp) Ctrl-F / Ctrl-B search the print history, see
+// scrollback.rs.
+
+use crate::{Editor, Result};
+use crossterm::{
+    event::{KeyCode, KeyModifiers},
+    style::{Color, ContentStyle},
+};
+use grapheme_utils::*;
+use regex::{Regex, bytes};
+use std::ops::Range;
+
+// What is being searched, and where the search started
+pub(crate) enum Target {
+    Edit {
+        origin: (usize, usize, usize, usize), // lineidx, lidx, scrollstart, wraptop
+    },
+    Scrollback {
+        origin: (usize, usize),      // hb_start_index, hb_end_index
+        found: Option<Range<usize>>, // Match shown, as history indices
+        copy: (usize, Vec<u8>),      // History searched, from history index copy.0
+    },
+    Input {
+        origin: (usize, String, usize, usize), // history pos, text, lineidx, lidx
+        found: Option<(usize, usize)>,         // Match shown, as entry and byte index
+    },
+}
+
+pub(crate) struct Search {
+    query: String,
+    re: Option<Regex>,              // Compiled query, None when empty or invalid
+    re_bytes: Option<bytes::Regex>, // The same, to search the print history
+    regex: bool,                    // Alt-R: The query is a regex, not literal text
+    pub(crate) backward: bool,
+    pub(crate) failing: bool, // Nothing matches the query
+    pub(crate) target: Target,
+}
+
+impl Search {
+    pub(crate) fn new(backward: bool, target: Target) -> Self {
+        Self {
+            query: String::new(),
+            re: None,
+            re_bytes: None,
+            regex: false,
+            backward,
+            failing: false,
+            target,
+        }
+    }
+
+    fn compile(&mut self) {
+        self.re = if self.query.is_empty() {
+            None
+        } else {
+            Regex::new(&self.pattern()).ok()
+        };
+        self.re_bytes = match (&self.re, &self.target) {
+            (Some(_), Target::Scrollback { .. }) => bytes::Regex::new(&self.pattern()).ok(),
+            _ => None,
+        };
+    }
+
+    /// Byte ranges of the query in the print history
+    pub(crate) fn find_bytes(&self, hist: &[u8]) -> Vec<Range<usize>> {
+        let Some(re) = &self.re_bytes else {
+            return Vec::new();
+        };
+        re.find_iter(hist)
+            .filter(|m| !m.is_empty())
+            .map(|m| m.range())
+            .collect()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.query.is_empty()
+    }
+
+    // Byte ranges of the query in line, on grapheme boundaries
+    pub(crate) fn matches(&self, line: &str) -> Vec<Range<usize>> {
+        let Some(re) = &self.re else {
+            return Vec::new();
+        };
+        re.find_iter(line)
+            .filter(|m| {
+                !m.is_empty()
+                    && grapheme_idx_at_idx(line, m.start()) == m.start()
+                    && grapheme_idx_at_idx(line, m.end()) == m.end()
+            })
+            .map(|m| m.range())
+            .collect()
+    }
+
+    // The query as a regex
+    fn pattern(&self) -> String {
+        if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        }
+    }
+
+    /// Split bar text while searching
+    pub(crate) fn prompt(&self) -> String {
+        format!(
+            "=====  {}{}{}Search{}: {}  ",
+            if self.failing { "Failing " } else { "" },
+            if self.regex { "Regex " } else { "" },
+            match self.target {
+                Target::Edit { .. } => "",
+                Target::Scrollback { .. } => "History ",
+                Target::Input { .. } => "Input History ",
+            },
+            if self.backward { " backward" } else { "" },
+            self.query
+        )
+    }
+}
+
+impl Editor {
+    // Find the next match from pos, wrapping around the text
+    fn find_match(&self, pos: (usize, usize), inclusive: bool) -> Option<(usize, usize)> {
+        let search = self.search.as_ref()?;
+        let num = self.lines.len();
+        if search.backward {
+            let lines = (0..=pos.0).rev().chain((pos.0..num).rev());
+            for (n, lineidx) in lines.enumerate() {
+                let matches = search.matches(&self.lines[lineidx]);
+                let found = matches
+                    .iter()
+                    .rev()
+                    .find(|m| n > 0 || m.start < pos.1 || (inclusive && m.start == pos.1));
+                if let Some(m) = found {
+                    return Some((lineidx, m.start));
+                }
+            }
+        } else {
+            let lines = (pos.0..num).chain(0..=pos.0);
+            for (n, lineidx) in lines.enumerate() {
+                let matches = search.matches(&self.lines[lineidx]);
+                let found = matches
+                    .iter()
+                    .find(|m| n > 0 || m.start > pos.1 || (inclusive && m.start == pos.1));
+                if let Some(m) = found {
+                    return Some((lineidx, m.start));
+                }
+            }
+        }
+        None
+    }
+
+    // Search from the current match, or from the start over, and show the match
+    fn search_find(&mut self, inclusive: bool, restart: bool) -> Result<()> {
+        let Some(search) = &self.search else {
+            return Ok(());
+        };
+        let origin = match search.target {
+            Target::Edit { origin } => origin,
+            Target::Scrollback { .. } => return self.scrollback_find(inclusive, restart),
+            Target::Input { .. } => return self.history_find(inclusive, restart),
+        };
+        let pos = if restart || search.is_empty() {
+            (origin.0, origin.1)
+        } else {
+            (self.lineidx, self.lidx)
+        };
+        if search.is_empty() {
+            // Nothing to find, back to the start
+            return self.search_goto(Some(pos));
+        }
+        let found = self.find_match(pos, inclusive);
+        self.search_goto(found)
+    }
+
+    // Move to the match found, or mark the search failing
+    fn search_goto(&mut self, found: Option<(usize, usize)>) -> Result<()> {
+        if let Some(search) = &mut self.search {
+            search.failing = found.is_none() && !search.is_empty();
+        }
+        if let Some((lineidx, lidx)) = found {
+            self.lineidx = lineidx;
+            self.lidx = lidx;
+            self.loose_cursor = true;
+            self.setrow();
+            self.setpos()?;
+        }
+        self.redraw()?;
+        Ok(())
+    }
+
+    /// Handle a key while searching, returns false if the key ended the search
+    /// and still needs to be handled
+    pub(crate) fn search_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Result<bool> {
+        let Some(search) = &mut self.search else {
+            return Ok(false);
+        };
+        match (code, modifiers) {
+            (KeyCode::Char('f'), KeyModifiers::CONTROL) => self.search_next(false)?,
+            (KeyCode::Char('b' | 'r'), KeyModifiers::CONTROL) => self.search_next(true)?,
+            (KeyCode::Char('g'), KeyModifiers::CONTROL) | (KeyCode::Esc, _) => {
+                // Back to where the search started
+                match self.search.take().map(|search| search.target) {
+                    Some(Target::Edit { origin }) => {
+                        (self.lineidx, self.lidx, self.scrollstart, self.wraptop) = origin;
+                        self.setrow();
+                        self.setpos()?;
+                        self.redraw()?;
+                    }
+                    Some(Target::Scrollback { origin, .. }) => {
+                        self.scrollback_page(origin.0, None)?;
+                    }
+                    Some(Target::Input { origin, .. }) => {
+                        let (pos, text, lineidx, lidx) = origin;
+                        self.history_goto(pos, &text, (lineidx, lidx))?;
+                    }
+                    None => {}
+                }
+            }
+            (KeyCode::Char('r'), KeyModifiers::ALT) => {
+                search.regex = !search.regex;
+                search.compile();
+                self.search_find(true, true)?;
+            }
+            (KeyCode::Enter, _) => self.search_stop()?,
+            (KeyCode::Backspace, _) => {
+                search.query.pop();
+                search.compile();
+                // Start over, the cursor may have passed closer matches
+                self.search_find(true, true)?;
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                search.query.push(c);
+                search.compile();
+                self.search_find(true, false)?;
+            }
+            _ => {
+                self.search_stop()?;
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Ctrl-F / Ctrl-B: Step to the next match, or start a search
+    pub(crate) fn search_next(&mut self, backward: bool) -> Result<()> {
+        let Some(search) = &mut self.search else {
+            let target = if self.hb_active {
+                Target::Scrollback {
+                    origin: (self.hb_start_index, self.hb_end_index),
+                    found: None,
+                    copy: (0, Vec::new()),
+                }
+            } else {
+                self.anchor = None;
+                Target::Edit {
+                    origin: (self.lineidx, self.lidx, self.scrollstart, self.wraptop),
+                }
+            };
+            self.search = Some(Search::new(backward, target));
+            self.redraw()?;
+            return Ok(());
+        };
+        search.backward = backward;
+        if search.query.is_empty() {
+            // Search for the last query again
+            search.query = self.last_search.clone();
+            search.compile();
+        }
+        self.search_find(false, false)
+    }
+
+    // Paste into the query
+    pub(crate) fn search_paste(&mut self, text: &str) -> Result<()> {
+        if let Some(search) = &mut self.search {
+            search.query.push_str(text.lines().next().unwrap_or(""));
+            search.compile();
+            self.search_find(true, false)?;
+        }
+        Ok(())
+    }
+
+    // End the search, leaving the cursor at the match
+    fn search_stop(&mut self) -> Result<()> {
+        if let Some(search) = self.search.take() {
+            if !search.query.is_empty() {
+                self.last_search = search.query;
+            }
+            self.redraw()?;
+        }
+        Ok(())
+    }
+
+    // Search highlights for a line, the match at the cursor stands out
+    pub(crate) fn search_styles(&self, lineidx: usize) -> Vec<(Range<usize>, ContentStyle)> {
+        let Some(search) = &self.search else {
+            return Vec::new();
+        };
+        if matches!(search.target, Target::Scrollback { .. }) {
+            return Vec::new();
+        }
+        let mut style = ContentStyle::new();
+        style.background_color = Some(Color::DarkGrey);
+        let mut current = ContentStyle::new();
+        current.background_color = Some(Color::Yellow);
+        current.foreground_color = Some(Color::Black);
+        search
+            .matches(&self.lines[lineidx])
+            .into_iter()
+            .map(|m| {
+                if !search.failing && (lineidx, m.start) == (self.lineidx, self.lidx) {
+                    (m, current)
+                } else {
+                    (m, style)
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,85 @@
+// Text Selection
+//
+// The selection runs from the anchor, set when a Shift-motion starts, to the
+// cursor (self.lineidx, self.lidx).  Either end may come first, and the
+// selection may cover many lines.
+
+use crate::{CmdKind, Editor, Result};
+use std::ops::Range;
+
+impl Editor {
+    pub(crate) fn copy(&mut self) {
+        if let Some((start, end)) = self.selection_range() {
+            self.clipboard = self.text_range(start, end);
+        }
+    }
+
+    pub(crate) fn cut(&mut self) -> Result<()> {
+        if let Some((start, end)) = self.selection_range() {
+            self.save_undo(CmdKind::Edit);
+            self.anchor = None;
+            self.clipboard = self.delete_range(start, end)?;
+            self.redraw()?;
+        }
+        Ok(())
+    }
+
+    /// Delete the selected text, returns false if nothing is selected
+    ///
+    /// The caller is responsible for the redraw
+    pub(crate) fn delete_selection(&mut self) -> Result<bool> {
+        let Some((start, end)) = self.selection_range() else {
+            self.anchor = None;
+            return Ok(false);
+        };
+        self.save_undo(CmdKind::Edit);
+        self.anchor = None;
+        self.delete_range(start, end)?;
+        Ok(true)
+    }
+
+    /// Paste text over the selection as a single undo step
+    pub(crate) fn paste(&mut self, text: &str) -> Result<()> {
+        self.delete_selection()?;
+        if !text.is_empty() {
+            self.save_undo(CmdKind::Edit);
+            self.insert_text(text)?;
+        }
+        self.redraw()?;
+        Ok(())
+    }
+
+    /// Ordered (start, end) positions of the selection, or None when empty
+    pub(crate) fn selection_range(&self) -> Option<((usize, usize), (usize, usize))> {
+        let anchor = self.anchor?;
+        let anchor = self.clamp_pos(anchor);
+        let cursor = self.clamp_pos((self.lineidx, self.lidx));
+        if self.vi_visual_mode() {
+            // Through the grapheme at the far end
+            let (start, mut end) = if anchor < cursor {
+                (anchor, cursor)
+            } else {
+                (cursor, anchor)
+            };
+            end.1 = grapheme_utils::next_grapheme_idx_from_idx(&self.lines[end.0], end.1);
+            return (start != end).then_some((start, end));
+        }
+        match anchor.cmp(&cursor) {
+            std::cmp::Ordering::Less => Some((anchor, cursor)),
+            std::cmp::Ordering::Greater => Some((cursor, anchor)),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    // Selected byte range within one line.  The range ends at usize::MAX
+    // when the selection continues on the next line.
+    pub(crate) fn selection_on_line(&self, lineidx: usize) -> Option<Range<usize>> {
+        let (start, end) = self.selection_range()?;
+        if lineidx < start.0 || lineidx > end.0 {
+            return None;
+        }
+        let from = if lineidx == start.0 { start.1 } else { 0 };
+        let to = if lineidx == end.0 { end.1 } else { usize::MAX };
+        Some(from..to)
+    }
+}
@@ -0,0 +1,104 @@
+// Undo / Redo for the edit area
+//
+// Every undo step is a snapshot of the edit lines together with the
+// cursor.  Consecutive typing (or deleting) is grouped into a single step
+// by simply not taking another snapshot while the previous key did the
+// same kind of edit.  The lines are a Rope, so a snapshot only copies a
+// pointer per chunk of lines.
+
+use crate::{CmdKind, Editor, Result, rope::Rope};
+use std::collections::VecDeque;
+
+const UNDO_LIMIT: usize = 200;
+
+pub(crate) struct UndoState {
+    lines: Rope,
+    lineidx: usize,
+    lidx: usize,
+    lofs: usize,
+    scrollstart: usize,
+}
+
+#[derive(Default)]
+pub(crate) struct UndoStack {
+    undo: VecDeque<UndoState>,
+    redo: Vec<UndoState>,
+    group: Option<bool>, // Grouping keys into one step, and whether it is saved
+}
+
+impl UndoStack {
+    fn push(&mut self, state: UndoState) {
+        self.redo.clear();
+        self.undo.push_back(state);
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+    }
+}
+
+impl Editor {
+    fn undo_state(&self) -> UndoState {
+        UndoState {
+            lines: self.lines.clone(),
+            lineidx: self.lineidx,
+            lidx: self.lidx,
+            lofs: self.lofs,
+            scrollstart: self.scrollstart,
+        }
+    }
+
+    /// Save an undo step, call BEFORE changing self.lines
+    ///
+    /// A key saves at most one step.  Typing and Deleting are grouped with
+    /// the previous key when it did the same thing, and the keys of an
+    /// undo_group() with the first of them.
+    pub(crate) fn save_undo(&mut self, kind: CmdKind) {
+        let grouped = self.this_cmd != CmdKind::Other
+            || (kind == self.last_cmd && matches!(kind, CmdKind::Insert | CmdKind::Delete))
+            || self.undo_stack.group == Some(true);
+        self.this_cmd = kind;
+        if let Some(saved) = &mut self.undo_stack.group {
+            *saved = true;
+        }
+        if !grouped {
+            let state = self.undo_state();
+            self.undo_stack.push(state);
+        }
+    }
+
+    // Make the keys until undo_group(false) one step, as vi's . does
+    pub(crate) fn undo_group(&mut self, group: bool) {
+        self.undo_stack.group = group.then_some(false);
+    }
+
+    pub(crate) fn undo(&mut self) -> Result<()> {
+        if let Some(state) = self.undo_stack.undo.pop_back() {
+            let current = self.undo_state();
+            self.undo_stack.redo.push(current);
+            self.restore_undo_state(state)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn redo(&mut self) -> Result<()> {
+        if let Some(state) = self.undo_stack.redo.pop() {
+            let current = self.undo_state();
+            self.undo_stack.undo.push_back(current);
+            self.restore_undo_state(state)?;
+        }
+        Ok(())
+    }
+
+    fn restore_undo_state(&mut self, state: UndoState) -> Result<()> {
+        self.anchor = None;
+        self.lines = state.lines;
+        self.lineidx = state.lineidx.min(self.lines.len().saturating_sub(1));
+        self.lidx = state.lidx;
+        self.lofs = state.lofs;
+        self.scrollstart = state.scrollstart;
+        self.setrow();
+        self.setpos()?;
+        self.redraw()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+// Input Validation
+//
+// With a Validator set, submitting (Action::Submit) first asks it about the
+// whole text.  Valid text is submitted as usual.  Incomplete text gets a new
+// line instead, as if Alt-Enter had been pressed, so the user can go on
+// typing.  Invalid text stays in the editor, with the message shown in the
+// split bar and the offending line, if any, marked in red, until the next
+// key.
+
+use crate::{Editor, Result};
+use crossterm::style::{Color, ContentStyle};
+use std::ops::Range;
+
+/// What a Validator makes of the text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Validation {
+    Valid,
+    /// Not finished yet, Enter starts a new line
+    Incomplete,
+    /// Not to be submitted, with a message and the offending line
+    Invalid {
+        message: String,
+        line: Option<usize>,
+    },
+}
+
+/// Checks text before it is submitted
+pub trait Validator {
+    fn validate(&mut self, text: &str) -> Validation;
+}
+
+impl<F: FnMut(&str) -> Validation> Validator for F {
+    fn validate(&mut self, text: &str) -> Validation {
+        self(text)
+    }
+}
+
+// Why the text was not submitted
+pub(crate) struct Invalid {
+    message: String,
+    line: Option<usize>,
+}
+
+impl Invalid {
+    /// Split bar text while the message is shown
+    pub(crate) fn prompt(&self) -> String {
+        format!("=====  Invalid: {}  ", self.message)
+    }
+}
+
+impl Editor {
+    // Mark the offending line
+    pub(crate) fn invalid_styles(&self, lineidx: usize) -> Vec<(Range<usize>, ContentStyle)> {
+        match &self.invalid {
+            Some(invalid) if invalid.line == Some(lineidx) => {
+                let mut style = ContentStyle::new();
+                style.background_color = Some(Color::DarkRed);
+                vec![(0..self.lines[lineidx].len() + 1, style)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    // Ask the validator about the text, showing why when it is invalid
+    pub(crate) fn validate(&mut self) -> Result<Validation> {
+        let text = self.lines.text();
+        let Some(validator) = &mut self.validator else {
+            return Ok(Validation::Valid);
+        };
+        let validation = validator.validate(&text);
+        if let Validation::Invalid { message, line } = &validation {
+            self.invalid = Some(Invalid {
+                message: message.clone(),
+                line: *line,
+            });
+            self.redraw()?;
+        }
+        Ok(validation)
+    }
+}
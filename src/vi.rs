@@ -0,0 +1,647 @@
+// Vi Modal Editing
+//
+// set_vi_mode(true) turns on vi style editing.  It starts in insert mode,
+// where keys work as usual, and Esc switches to normal mode, where keys are
+// commands:
+//
+// - Motions: h j k l, w b e, 0 ^ $, gg G, each with an optional count
+// - Operators: d c y followed by a motion, dd cc yy for whole lines
+// - x X D C s Y r p P, u / Ctrl-R to undo / redo
+// - i a I A o O: Back to insert mode
+// - v: Visual mode, motions extend the selection and d x c s y act on it
+// - .: Repeat the last change, with the new count if one is typed
+//
+// The mode, and any count or operator typed so far, is shown in the split
+// bar.  Ctrl and Alt keys, arrows, Home, End and paging work in every mode,
+// and so does Enter when it is bound to Submit.
+// Yanked and deleted text goes to a register of its own, not the clipboard.
+//
+// w b e move by vi words, not the Unicode words of Ctrl-Left / Ctrl-Right:
+// a word is a run of keyword characters (letters, digits and the word
+// characters, _ by default) or a run of other non-blank characters, so
+// foo.bar is three words, and an empty line is a word of its own.
+//
+// A change is repeated by replaying its keys, from the count through the
+// Esc that ends any text typed in insert mode.  The replayed change is one
+// undo step, however many steps typing it took.
+
+use crate::{Action, CmdKind, Editor, Result, idx_at_col};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use grapheme_utils::*;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Mode {
+    Normal,
+    Insert,
+    Visual,
+}
+
+// What a normal mode key did
+#[derive(PartialEq)]
+enum Done {
+    Pending, // Waiting for more keys
+    Move,    // Moved the cursor, or anything else not repeated by .
+    Change,  // Changed the text
+    Insert,  // Started insert mode, the change goes on until Esc
+}
+
+// What a grapheme is to w b e
+#[derive(Clone, Copy, PartialEq)]
+enum Class {
+    Blank,
+    Keyword, // Letters, digits and the word characters
+    Other,   // Any other non-blank
+}
+
+// Where a motion goes
+struct Motion {
+    pos: (usize, usize),
+    linewise: bool,  // Operators work on whole lines (j k gg G)
+    inclusive: bool, // Operators include the grapheme at pos (e)
+}
+
+pub(crate) struct Vi {
+    pub(crate) mode: Mode,
+    count: Option<usize>,              // Count typed so far
+    op: Option<(char, Option<usize>)>, // Operator waiting for a motion, and its count
+    prefix: Option<char>,              // g or r waiting for the next key
+    keys: Vec<KeyEvent>,               // Keys of the change being typed
+    last_change: Vec<KeyEvent>,        // Keys of the last change, for .
+    register: String,
+    linewise: bool, // The register holds whole lines
+    replaying: bool,
+}
+
+impl Vi {
+    fn new() -> Self {
+        Self {
+            mode: Mode::Insert,
+            count: None,
+            op: None,
+            prefix: None,
+            keys: Vec::new(),
+            last_change: Vec::new(),
+            register: String::new(),
+            linewise: false,
+            replaying: false,
+        }
+    }
+
+    /// Split bar text: the mode, then the count and operator typed so far
+    pub(crate) fn indicator(&self) -> String {
+        let mode = match self.mode {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Visual => "VISUAL",
+        };
+        let mut pending = String::new();
+        if let Some((op, Some(count))) = self.op {
+            pending.push_str(&format!("{count}{op}"));
+        } else if let Some((op, None)) = self.op {
+            pending.push(op);
+        }
+        if let Some(count) = self.count {
+            pending.push_str(&count.to_string());
+        }
+        pending.extend(self.prefix);
+        if pending.is_empty() {
+            mode.to_string()
+        } else {
+            format!("{mode} {pending}")
+        }
+    }
+
+    fn reset(&mut self) {
+        self.count = None;
+        self.op = None;
+        self.prefix = None;
+        self.keys.clear();
+    }
+}
+
+// Byte ranges of the vi words in line: runs of graphemes of one class
+fn vi_word_ranges(line: &str, word_chars: &str) -> Vec<Range<usize>> {
+    let mut words: Vec<Range<usize>> = Vec::new();
+    let mut prev = Class::Blank;
+    for (idx, g) in line.grapheme_indices(true) {
+        let c = g.chars().next().unwrap_or(' ');
+        let class = if c.is_whitespace() {
+            Class::Blank
+        } else if c.is_alphanumeric() || word_chars.contains(c) {
+            Class::Keyword
+        } else {
+            Class::Other
+        };
+        let end = idx + g.len();
+        match words.last_mut() {
+            _ if class == Class::Blank => {}
+            Some(word) if class == prev => word.end = end,
+            _ => words.push(idx..end),
+        }
+        prev = class;
+    }
+    if line.is_empty() {
+        words.push(0..0);
+    }
+    words
+}
+
+impl Editor {
+    /// Vi style modal editing, starting in insert mode
+    pub fn set_vi_mode(&mut self, vi_mode: bool) -> Result<()> {
+        self.vi = if vi_mode { Some(Vi::new()) } else { None };
+        self.anchor = None;
+        self.redraw()
+    }
+
+    // Leading whitespace length of a line
+    fn first_non_blank(&self, lineidx: usize) -> usize {
+        let line = &self.lines[lineidx];
+        line.len() - line.trim_start_matches([' ', '\t']).len()
+    }
+
+    // Normal mode key, after any g or r prefix and count
+    fn vi_command(&mut self, c: char) -> Result<Done> {
+        let Some(vi) = &mut self.vi else {
+            return Ok(Done::Move);
+        };
+        let c = match vi.prefix.take() {
+            Some('r') => {
+                let count = vi.count.take().unwrap_or(1);
+                return self.vi_replace_chars(c, count);
+            }
+            // gg
+            Some(_) if c == 'g' => 'g',
+            Some(_) => {
+                vi.reset();
+                return Ok(Done::Move);
+            }
+            None if c == 'g' || (c == 'r' && vi.op.is_none() && vi.mode == Mode::Normal) => {
+                vi.prefix = Some(c);
+                return Ok(Done::Pending);
+            }
+            None => c,
+        };
+        if let Some(digit) = c.to_digit(10).filter(|d| *d > 0 || vi.count.is_some()) {
+            vi.count = Some(vi.count.unwrap_or(0) * 10 + digit as usize);
+            return Ok(Done::Pending);
+        }
+        let count = vi.count.take();
+        if let Some((op, op_count)) = vi.op.take() {
+            let count = match (op_count, count) {
+                (None, None) => None,
+                (a, b) => Some(a.unwrap_or(1) * b.unwrap_or(1)),
+            };
+            return self.vi_operator(op, c, count);
+        }
+        if vi.mode == Mode::Visual {
+            return self.vi_visual(c, count);
+        }
+
+        let n = count.unwrap_or(1);
+        match c {
+            'c' | 'd' | 'y' => {
+                vi.op = Some((c, count));
+                Ok(Done::Pending)
+            }
+            'x' => self.vi_operator('d', 'l', count),
+            'X' => self.vi_operator('d', 'h', count),
+            'D' => self.vi_operator('d', '$', count),
+            'C' => self.vi_operator('c', '$', count),
+            's' => self.vi_operator('c', 'l', count),
+            'Y' => self.vi_operator('y', 'y', count),
+            'p' => self.vi_put(true, n),
+            'P' => self.vi_put(false, n),
+            'u' => {
+                for _ in 0..n {
+                    self.undo()?;
+                }
+                Ok(Done::Move)
+            }
+            'i' => self.vi_insert(),
+            'a' => {
+                self.lidx = self.next_grapheme_idx_from_idx(self.lidx);
+                self.vi_insert()
+            }
+            'I' => {
+                self.lidx = self.first_non_blank(self.lineidx);
+                self.vi_insert()
+            }
+            'A' => {
+                self.lidx = self.len();
+                self.vi_insert()
+            }
+            'o' => {
+                self.save_undo(CmdKind::Edit);
+                self.lidx = self.len();
+                self.insert_text("\n")?;
+                self.vi_insert()
+            }
+            'O' => {
+                self.save_undo(CmdKind::Edit);
+                self.lidx = 0;
+                self.insert_text("\n")?;
+                self.lineidx -= 1;
+                self.lidx = 0;
+                self.vi_insert()
+            }
+            'v' => {
+                vi.mode = Mode::Visual;
+                self.anchor = Some(self.clamp_pos((self.lineidx, self.lidx)));
+                Ok(Done::Move)
+            }
+            '.' => self.vi_repeat(count),
+            _ => {
+                self.vi_move(c, count);
+                Ok(Done::Move)
+            }
+        }
+    }
+
+    // Clamp the cursor onto the line in normal mode, and show it
+    fn vi_done(&mut self) -> Result<()> {
+        (self.lineidx, self.lidx) = self.clamp_pos((self.lineidx, self.lidx));
+        if self.vi.as_ref().is_some_and(|vi| vi.mode != Mode::Insert) && self.lidx == self.len() {
+            self.lidx = self.prev_grapheme_idx_from_idx(self.lidx);
+        }
+        self.setrow();
+        self.setpos()?;
+        self.redraw()
+    }
+
+    fn vi_insert(&mut self) -> Result<Done> {
+        if let Some(vi) = &mut self.vi {
+            vi.mode = Mode::Insert;
+        }
+        Ok(Done::Insert)
+    }
+
+    /// Handle a key in vi mode, returns false if the key is handled as usual
+    pub(crate) fn vi_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Result<bool> {
+        let Some(vi) = &mut self.vi else {
+            return Ok(false);
+        };
+        if self.hb_active {
+            return Ok(false);
+        }
+        let key = KeyEvent::new(code, modifiers);
+        if vi.mode == Mode::Insert {
+            if !vi.keys.is_empty() && !vi.replaying {
+                vi.keys.push(key);
+            }
+            if code != KeyCode::Esc {
+                return Ok(false);
+            }
+            vi.mode = Mode::Normal;
+            let keys = std::mem::take(&mut vi.keys);
+            if !keys.is_empty() && !vi.replaying {
+                vi.last_change = keys;
+            }
+            self.lidx = self.prev_grapheme_idx_from_idx(self.lidx);
+            self.vi_done()?;
+            return Ok(true);
+        }
+
+        let c = match (code, modifiers) {
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => c,
+            (KeyCode::Backspace, _) => 'h',
+            (KeyCode::Delete, _) => 'x',
+            (KeyCode::Enter, _)
+                if self
+                    .keymap
+                    .action(code, modifiers)
+                    .is_some_and(|(action, _)| action == Action::Submit) =>
+            {
+                // Submit mode: Enter sends from normal mode too
+                vi.reset();
+                return Ok(false);
+            }
+            (KeyCode::Enter, _) => 'j',
+            (KeyCode::Esc, _) => {
+                vi.reset();
+                vi.mode = Mode::Normal;
+                self.anchor = None;
+                self.vi_done()?;
+                return Ok(true);
+            }
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                let count = vi.count.unwrap_or(1);
+                vi.reset();
+                for _ in 0..count {
+                    self.redo()?;
+                }
+                self.vi_done()?;
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        };
+        if !vi.replaying {
+            vi.keys.push(key);
+        }
+        let done = self.vi_command(c)?;
+        if let Some(vi) = &mut self.vi {
+            match done {
+                Done::Pending | Done::Insert => {}
+                Done::Move => vi.keys.clear(),
+                Done::Change => {
+                    let keys = std::mem::take(&mut vi.keys);
+                    if !vi.replaying {
+                        vi.last_change = keys;
+                    }
+                }
+            }
+        }
+        self.vi_done()?;
+        Ok(true)
+    }
+
+    // Where motion c goes count times, or None when c is no motion
+    fn vi_motion(&self, c: char, count: Option<usize>) -> Option<Motion> {
+        let n = count.unwrap_or(1);
+        let (lineidx, lidx) = self.clamp_pos((self.lineidx, self.lidx));
+        let last = self.lines.len() - 1;
+        let line = &self.lines[lineidx];
+        let (pos, linewise, inclusive) = match c {
+            'h' => {
+                let idx = (0..n).fold(lidx, |idx, _| prev_grapheme_idx_from_idx(line, idx));
+                ((lineidx, idx), false, false)
+            }
+            'l' => {
+                let idx = (0..n).fold(lidx, |idx, _| next_grapheme_idx_from_idx(line, idx));
+                ((lineidx, idx), false, false)
+            }
+            '0' => ((lineidx, 0), false, false),
+            '^' => ((lineidx, self.first_non_blank(lineidx)), false, false),
+            '$' => {
+                let lineidx = (lineidx + n - 1).min(last);
+                ((lineidx, self.lines[lineidx].len()), false, false)
+            }
+            'w' => {
+                let pos = (0..n).fold((lineidx, lidx), |pos, _| {
+                    self.next_word_start(pos, vi_word_ranges)
+                });
+                (pos, false, false)
+            }
+            'b' => {
+                let pos = (0..n).fold((lineidx, lidx), |pos, _| {
+                    self.prev_word_start(pos, vi_word_ranges)
+                });
+                (pos, false, false)
+            }
+            'e' => {
+                let pos = (0..n).fold((lineidx, lidx), |pos, _| {
+                    let next = next_grapheme_idx_from_idx(&self.lines[pos.0], pos.1);
+                    let end = self.next_word_end((pos.0, next), vi_word_ranges);
+                    (end.0, prev_grapheme_idx_from_idx(&self.lines[end.0], end.1))
+                });
+                (pos, false, true)
+            }
+            'j' => (((lineidx + n).min(last), lidx), true, false),
+            'k' => ((lineidx.saturating_sub(n), lidx), true, false),
+            'g' | 'G' => {
+                let default = if c == 'g' { 0 } else { last };
+                let lineidx = count.map_or(default, |n| n - 1).min(last);
+                ((lineidx, self.first_non_blank(lineidx)), true, false)
+            }
+            _ => return None,
+        };
+        Some(Motion {
+            pos,
+            linewise,
+            inclusive,
+        })
+    }
+
+    // Move the cursor by motion c, up and down keeping the goal column
+    fn vi_move(&mut self, c: char, count: Option<usize>) {
+        let Some(motion) = self.vi_motion(c, count) else {
+            return;
+        };
+        (self.lineidx, self.lidx) = motion.pos;
+        if matches!(c, 'j' | 'k') {
+            self.set_goal_col();
+            self.lidx = idx_at_col(
+                &self.lines[self.lineidx],
+                self.goal_col,
+                self.tabstop as usize,
+            );
+        }
+    }
+
+    // Delete, change or yank from start to end
+    fn vi_operate(&mut self, op: char, start: (usize, usize), end: (usize, usize)) -> Result<Done> {
+        if start == end && op != 'c' {
+            return Ok(Done::Move);
+        }
+        let text = self.text_range(start, end);
+        if let Some(vi) = &mut self.vi {
+            vi.register = text;
+            vi.linewise = false;
+        }
+        (self.lineidx, self.lidx) = start;
+        if op == 'y' {
+            return Ok(Done::Move);
+        }
+        self.save_undo(CmdKind::Edit);
+        self.delete_range(start, end)?;
+        if op == 'c' {
+            self.vi_insert()
+        } else {
+            Ok(Done::Change)
+        }
+    }
+
+    // Delete, change or yank the lines first to last
+    fn vi_operate_lines(&mut self, op: char, first: usize, last: usize) -> Result<Done> {
+        let end = (last, self.lines[last].len());
+        let text = self.text_range((first, 0), end);
+        if let Some(vi) = &mut self.vi {
+            vi.register = text;
+            vi.linewise = true;
+        }
+        match op {
+            'y' => {
+                self.lineidx = first;
+                Ok(Done::Move)
+            }
+            'c' => {
+                self.save_undo(CmdKind::Edit);
+                self.delete_range((first, 0), end)?;
+                self.vi_insert()
+            }
+            _ => {
+                self.save_undo(CmdKind::Edit);
+                if last + 1 < self.lines.len() {
+                    self.delete_range((first, 0), (last + 1, 0))?;
+                } else if first > 0 {
+                    self.delete_range((first - 1, self.lines[first - 1].len()), end)?;
+                } else {
+                    self.delete_range((0, 0), end)?;
+                }
+                self.lidx = self.first_non_blank(self.lineidx);
+                Ok(Done::Change)
+            }
+        }
+    }
+
+    // Operator op with motion c: dd, dw, c$ ...
+    fn vi_operator(&mut self, op: char, c: char, count: Option<usize>) -> Result<Done> {
+        let cursor = self.clamp_pos((self.lineidx, self.lidx));
+        if c == op {
+            let last = (cursor.0 + count.unwrap_or(1) - 1).min(self.lines.len() - 1);
+            return self.vi_operate_lines(op, cursor.0, last);
+        }
+        // cw changes to the end of the word, like ce
+        let in_word = vi_word_ranges(&self.lines[cursor.0], &self.word_chars)
+            .iter()
+            .any(|word| word.contains(&cursor.1));
+        let c = if op == 'c' && c == 'w' && in_word {
+            'e'
+        } else {
+            c
+        };
+        let Some(motion) = self.vi_motion(c, count) else {
+            return Ok(Done::Move);
+        };
+        if motion.linewise {
+            let first = cursor.0.min(motion.pos.0);
+            let last = cursor.0.max(motion.pos.0);
+            return self.vi_operate_lines(op, first, last);
+        }
+        let mut pos = motion.pos;
+        if c == 'w' && pos.0 > cursor.0 {
+            // dw stops at the end of the line
+            pos = (cursor.0, self.lines[cursor.0].len());
+        }
+        let (start, mut end) = if pos < cursor {
+            (pos, cursor)
+        } else {
+            (cursor, pos)
+        };
+        if motion.inclusive {
+            end.1 = next_grapheme_idx_from_idx(&self.lines[end.0], end.1);
+        }
+        self.vi_operate(op, start, end)
+    }
+
+    // p / P: Put the register after / before the cursor, count times
+    fn vi_put(&mut self, after: bool, count: usize) -> Result<Done> {
+        let Some((register, linewise)) = self
+            .vi
+            .as_ref()
+            .map(|vi| (vi.register.clone(), vi.linewise))
+        else {
+            return Ok(Done::Move);
+        };
+        if register.is_empty() && !linewise {
+            return Ok(Done::Move);
+        }
+        self.save_undo(CmdKind::Edit);
+        let lineidx = self.lineidx;
+        if linewise {
+            let text = vec![register.as_str(); count].join("\n");
+            if after {
+                self.lidx = self.len();
+                self.insert_text(&format!("\n{text}"))?;
+                self.lineidx = lineidx + 1;
+            } else {
+                self.lidx = 0;
+                self.insert_text(&format!("{text}\n"))?;
+                self.lineidx = lineidx;
+            }
+            self.lidx = self.first_non_blank(self.lineidx);
+        } else {
+            let text = register.repeat(count);
+            if after {
+                self.lidx = self.next_grapheme_idx_from_idx(self.lidx);
+            }
+            self.insert_text(&text)?;
+            self.lidx = self.prev_grapheme_idx_from_idx(self.lidx);
+        }
+        Ok(Done::Change)
+    }
+
+    // .: Replay the keys of the last change
+    fn vi_repeat(&mut self, count: Option<usize>) -> Result<Done> {
+        let Some(vi) = &mut self.vi else {
+            return Ok(Done::Move);
+        };
+        let mut keys = vi.last_change.clone();
+        if let Some(count) = count {
+            let digits = keys
+                .iter()
+                .take_while(|key| matches!(key.code, KeyCode::Char(c) if c.is_ascii_digit()))
+                .count();
+            let count = count.to_string();
+            let count = count
+                .chars()
+                .map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+            keys.splice(0..digits, count);
+        }
+        vi.replaying = true;
+        self.undo_group(true);
+        let replayed = keys
+            .into_iter()
+            .try_for_each(|key| self.handle_event(Event::Key(key)).map(|_| ()));
+        self.undo_group(false);
+        if let Some(vi) = &mut self.vi {
+            vi.replaying = false;
+        }
+        replayed?;
+        Ok(Done::Move)
+    }
+
+    // r: Replace count graphemes with c
+    fn vi_replace_chars(&mut self, c: char, count: usize) -> Result<Done> {
+        let (lineidx, start) = self.clamp_pos((self.lineidx, self.lidx));
+        let line = &self.lines[lineidx];
+        let mut end = start;
+        for _ in 0..count {
+            if end == line.len() {
+                return Ok(Done::Move);
+            }
+            end = next_grapheme_idx_from_idx(line, end);
+        }
+        self.save_undo(CmdKind::Edit);
+        self.delete_range((lineidx, start), (lineidx, end))?;
+        self.insert_text(&c.to_string().repeat(count))?;
+        self.lidx = self.prev_grapheme_idx_from_idx(self.lidx);
+        Ok(Done::Change)
+    }
+
+    // Visual mode key: a motion, or an operator on the selection
+    fn vi_visual(&mut self, c: char, count: Option<usize>) -> Result<Done> {
+        match c {
+            'v' => {
+                if let Some(vi) = &mut self.vi {
+                    vi.mode = Mode::Normal;
+                }
+                self.anchor = None;
+            }
+            'c' | 'd' | 's' | 'x' | 'y' => {
+                let cursor = self.clamp_pos((self.lineidx, self.lidx));
+                let (start, end) = self.selection_range().unwrap_or((cursor, cursor));
+                self.anchor = None;
+                if let Some(vi) = &mut self.vi {
+                    vi.mode = Mode::Normal;
+                }
+                let op = match c {
+                    's' => 'c',
+                    'x' => 'd',
+                    c => c,
+                };
+                // Not repeated by .
+                self.vi_operate(op, start, end)?;
+            }
+            _ => self.vi_move(c, count),
+        }
+        Ok(Done::Move)
+    }
+
+    /// In vi visual mode the selection includes the grapheme at the cursor
+    pub(crate) fn vi_visual_mode(&self) -> bool {
+        self.vi.as_ref().is_some_and(|vi| vi.mode == Mode::Visual)
+    }
+}
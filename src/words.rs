@@ -0,0 +1,218 @@
+// Word Motion
+//
+// Ctrl-Left / Ctrl-Right move by words found with the Unicode word boundary
+// rules (UAX #29), so print(foo) is two words while don't, 3.14 and
+// example.com are one each, and in CJK text, which has no spaces, each
+// ideograph is a word of its own.
+//
+// A word is a word boundary segment holding a letter or digit, kept whole.
+// Segments of the extra word characters (by default just _) count as words
+// too, and join the words on either side: with - added, foo-bar is one
+// word.  Other punctuation and whitespace between words are skipped.
+//
+// The readline word commands use the same words: Alt-D / Alt-Backspace kill
+// to the end / start of a word (see killring.rs), Alt-T swaps the words
+// around the cursor and Alt-U / Alt-L / Alt-C upcase / downcase / capitalize
+// from the cursor to the end of the word.  Ctrl-T swaps the graphemes
+// around the cursor.
+
+use crate::{CmdKind, Editor, Result};
+use grapheme_utils::*;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Alt-U / Alt-L / Alt-C
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Case {
+    Upper,
+    Lower,
+    Capital, // First letter upper, the rest lower
+}
+
+// Finds the byte ranges of the words in a line, given the word characters
+pub(crate) type WordRanges = fn(&str, &str) -> Vec<Range<usize>>;
+
+/// Byte ranges of the words in line
+pub(crate) fn word_ranges(line: &str, word_chars: &str) -> Vec<Range<usize>> {
+    let mut words: Vec<Range<usize>> = Vec::new();
+    let mut prev_joins: Option<bool> = None; // Previous segment, when a word
+    for (start, segment) in line.split_word_bound_indices() {
+        let joins = segment.chars().all(|c| word_chars.contains(c));
+        if !joins && !segment.chars().any(char::is_alphanumeric) {
+            prev_joins = None;
+            continue;
+        }
+        let end = start + segment.len();
+        match (prev_joins, words.last_mut()) {
+            // Joined by a word character
+            (Some(prev), Some(word)) if prev || joins => word.end = end,
+            _ => words.push(start..end),
+        }
+        prev_joins = Some(joins);
+    }
+    words
+}
+
+impl Editor {
+    // Alt-U / Alt-L / Alt-C: Change the case from the cursor to the end of the word
+    pub(crate) fn case_word(&mut self, case: Case) -> Result<()> {
+        self.anchor = None;
+        let start = self.clamp_pos((self.lineidx, self.lidx));
+        let end = self.next_word_end(start, word_ranges);
+        if start == end {
+            return Ok(());
+        }
+        self.save_undo(CmdKind::Edit);
+        let text = self.delete_range(start, end)?;
+        let mut capital = case == Case::Capital;
+        let text: String = text
+            .graphemes(true)
+            .map(|g| match case {
+                Case::Upper => g.to_uppercase(),
+                Case::Capital if capital => {
+                    if g.chars().any(char::is_alphanumeric) {
+                        capital = false;
+                        g.to_uppercase()
+                    } else {
+                        g.to_string()
+                    }
+                }
+                _ => g.to_lowercase(),
+            })
+            .collect();
+        self.insert_text(&text)?;
+        self.redraw()
+    }
+
+    // End of the word at or after pos, looking on through the following lines
+    pub(crate) fn next_word_end(&self, pos: (usize, usize), words: WordRanges) -> (usize, usize) {
+        for lineidx in pos.0..self.lines.len() {
+            let from = if lineidx == pos.0 { pos.1 } else { 0 };
+            let words = words(&self.lines[lineidx], &self.word_chars);
+            if let Some(word) = words.iter().find(|word| word.end > from) {
+                return (lineidx, word.end);
+            }
+        }
+        let last = self.lines.len() - 1;
+        (last, self.lines[last].len())
+    }
+
+    // Start of the word after pos, looking on through the following lines
+    pub(crate) fn next_word_start(&self, pos: (usize, usize), words: WordRanges) -> (usize, usize) {
+        for lineidx in pos.0..self.lines.len() {
+            let words = words(&self.lines[lineidx], &self.word_chars);
+            if let Some(word) = words
+                .iter()
+                .find(|word| lineidx > pos.0 || word.start > pos.1)
+            {
+                return (lineidx, word.start);
+            }
+        }
+        let last = self.lines.len() - 1;
+        (last, self.lines[last].len())
+    }
+
+    // Start of the word before pos, looking back through the previous lines
+    pub(crate) fn prev_word_start(&self, pos: (usize, usize), words: WordRanges) -> (usize, usize) {
+        for lineidx in (0..=pos.0).rev() {
+            let before = if lineidx == pos.0 { pos.1 } else { usize::MAX };
+            let words = words(&self.lines[lineidx], &self.word_chars);
+            if let Some(word) = words.iter().rev().find(|word| word.start < before) {
+                return (lineidx, word.start);
+            }
+        }
+        (0, 0)
+    }
+
+    // Ctrl-T: Swap the graphemes before and at the cursor, moving past them.
+    // At the end of the line, swap the two before the cursor
+    pub(crate) fn transpose_chars(&mut self) -> Result<()> {
+        self.anchor = None;
+        let (lineidx, mut idx) = self.clamp_pos((self.lineidx, self.lidx));
+        let line = &self.lines[lineidx];
+        if idx == line.len() {
+            idx = prev_grapheme_idx_from_idx(line, idx);
+        }
+        if idx == 0 {
+            return Ok(());
+        }
+        let start = prev_grapheme_idx_from_idx(line, idx);
+        let end = next_grapheme_idx_from_idx(line, idx);
+        let swapped = format!("{}{}", &line[idx..end], &line[start..idx]);
+        self.save_undo(CmdKind::Edit);
+        self.delete_range((lineidx, start), (lineidx, end))?;
+        self.insert_text(&swapped)?;
+        self.redraw()
+    }
+
+    // Alt-T: Swap the word before the cursor with the one after it, moving
+    // past them.  At the end of the line, swap the last two words
+    pub(crate) fn transpose_words(&mut self) -> Result<()> {
+        self.anchor = None;
+        let (lineidx, idx) = self.clamp_pos((self.lineidx, self.lidx));
+        let words = self.words();
+        if words.len() < 2 {
+            return Ok(());
+        }
+        let n = words
+            .iter()
+            .rposition(|word| word.start < idx)
+            .unwrap_or(0)
+            .min(words.len() - 2);
+        let (first, second) = (words[n].clone(), words[n + 1].clone());
+        let line = &self.lines[lineidx];
+        let swapped = format!(
+            "{}{}{}",
+            &line[second.clone()],
+            &line[first.end..second.start],
+            &line[first.clone()]
+        );
+        self.save_undo(CmdKind::Edit);
+        self.delete_range((lineidx, first.start), (lineidx, second.end))?;
+        self.insert_text(&swapped)?;
+        self.redraw()
+    }
+
+    // Ctrl-Left: Move to the start of this or the previous word
+    pub(crate) fn word_left(&mut self) -> Result<()> {
+        if self.lidx == 0 {
+            if self.lineidx == 0 {
+                return Ok(());
+            }
+            self.move_up(1, true)?;
+            self.lidx = self.len();
+        }
+        self.lidx = self
+            .words()
+            .iter()
+            .rev()
+            .map(|word| word.start)
+            .find(|start| *start < self.lidx)
+            .unwrap_or(0);
+        self.setpos()
+    }
+
+    // Ctrl-Right: Move to the start of the next word, or the end of the line
+    pub(crate) fn word_right(&mut self) -> Result<()> {
+        if self.lidx >= self.len() {
+            if self.lineidx + 1 >= self.lines.len() {
+                return Ok(());
+            }
+            self.move_down(1, true)?;
+            self.lidx = self.words().first().map_or(self.len(), |word| word.start);
+        } else {
+            self.lidx = self
+                .words()
+                .iter()
+                .map(|word| word.start)
+                .find(|start| *start > self.lidx)
+                .unwrap_or(self.len());
+        }
+        self.setpos()
+    }
+
+    // Words on the current line
+    pub(crate) fn words(&self) -> Vec<Range<usize>> {
+        word_ranges(&self.lines[self.lineidx], &self.word_chars)
+    }
+}
@@ -0,0 +1,284 @@
+// Soft Wrap Mode
+//
+// Long lines are wrapped onto several screen rows instead of scrolling
+// sideways.  The screen then starts at row self.wraptop of line
+// self.scrollstart, and the cursor row is counted in wrapped rows.
+//
+// Rows are broken after whitespace when possible, otherwise between
+// graphemes.  Tabs are measured from the start of their row.
+//
+// An edit draws only the rows of the cursor line again, unless the line now
+// takes more or fewer rows, which moves every line below it.
+
+use crate::{CmdKind, Editor, Result, display_width, tab_width};
+use crossterm::{QueueableCommand, cursor, terminal};
+use grapheme_utils::*;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Byte index where each wrapped row of a line starts
+pub(crate) fn wrap_line(line: &str, maxwidth: usize, tabstop: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut row_start = 0;
+    let mut brk = 0; // Break after the last whitespace
+    let mut width = 0;
+    for (i, g) in line.grapheme_indices(true) {
+        let mut char_width = tab_width(g, width, tabstop);
+        if width + char_width > maxwidth && i > row_start {
+            row_start = if brk > row_start { brk } else { i };
+            starts.push(row_start);
+            width = display_width(&line[row_start..i], tabstop);
+            char_width = tab_width(g, width, tabstop);
+        }
+        width += char_width;
+        if g.chars().all(char::is_whitespace) {
+            brk = i + g.len();
+        }
+    }
+    starts
+}
+
+// Row of the wrapped line holding idx
+pub(crate) fn row_of(starts: &[usize], idx: usize) -> usize {
+    starts.iter().rposition(|&start| start <= idx).unwrap_or(0)
+}
+
+impl Editor {
+    /// Wrap long lines onto several rows instead of scrolling sideways
+    pub fn set_soft_wrap(&mut self, soft_wrap: bool) -> Result<()> {
+        self.soft_wrap = soft_wrap;
+        self.lofs = 0;
+        self.wraptop = 0;
+        self.setrow();
+        self.setpos()?;
+        self.redraw()?;
+        Ok(())
+    }
+
+    // Index of the grapheme at column col of row r, the row end at most
+    fn idx_at_row_col(&self, starts: &[usize], r: usize, col: usize) -> usize {
+        let line = &self.lines[self.lineidx];
+        let end = starts.get(r + 1).copied().unwrap_or(line.len());
+        let mut width = 0;
+        for (i, g) in line[starts[r]..end].grapheme_indices(true) {
+            width += tab_width(g, width, self.tabstop as usize);
+            if width > col {
+                return starts[r] + i;
+            }
+        }
+        if r + 1 < starts.len() {
+            // Stay on this row, the row end is the start of the next one
+            prev_grapheme_idx_from_idx(line, end)
+        } else {
+            end
+        }
+    }
+
+    // Column to move to, the goal column while moving up and down
+    fn move_col(&self) -> usize {
+        if self.this_cmd == CmdKind::Vertical {
+            self.goal_col
+        } else {
+            (self.curx - self.gutter()) as usize
+        }
+    }
+
+    pub(crate) fn move_down_wrapped(&mut self, num: u16, move_to_beginning: bool) -> Result<()> {
+        let col = self.move_col();
+        let mut starts = self.wrap_rows(self.lineidx);
+        let mut r = row_of(&starts, self.lidx);
+        for n in 0..num {
+            if r + 1 < starts.len() {
+                r += 1;
+            } else if self.lineidx + 1 < self.lines.len() {
+                self.lineidx += 1;
+                starts = self.wrap_rows(self.lineidx);
+                r = 0;
+            } else {
+                if n == 0 {
+                    // Already on the bottom row
+                    self.lidx = self.len();
+                    self.setpos()?;
+                    self.redraw()?;
+                    return Ok(());
+                }
+                break;
+            }
+        }
+        self.lidx = if move_to_beginning {
+            0
+        } else {
+            self.idx_at_row_col(&starts, r, col)
+        };
+        self.setpos()?;
+        self.redraw()?;
+        Ok(())
+    }
+
+    pub(crate) fn move_up_wrapped(&mut self, num: u16, move_to_end: bool) -> Result<()> {
+        let col = self.move_col();
+        let mut starts = self.wrap_rows(self.lineidx);
+        let mut r = row_of(&starts, self.lidx);
+        for n in 0..num {
+            if r > 0 {
+                r -= 1;
+            } else if self.lineidx > 0 {
+                self.lineidx -= 1;
+                starts = self.wrap_rows(self.lineidx);
+                r = starts.len() - 1;
+            } else {
+                if n == 0 {
+                    // Already on the top row
+                    self.lidx = 0;
+                    self.setpos()?;
+                    self.redraw()?;
+                    return Ok(());
+                }
+                break;
+            }
+        }
+        self.lidx = if move_to_end {
+            self.len()
+        } else {
+            self.idx_at_row_col(&starts, r, col)
+        };
+        self.setpos()?;
+        self.redraw()?;
+        Ok(())
+    }
+
+    // Draw the edit lines, row by row, below the split
+    pub(crate) fn redraw_wrapped(&mut self) -> Result<()> {
+        let rows = self.sizey.saturating_sub(self.printlines + 2) as usize;
+        let maxwidth = self.maxwidth();
+        let mut row = 0;
+        let mut skip = self.wraptop;
+        self.wrapped = None;
+        for lidx in self.scrollstart..self.lines.len() {
+            let starts = self.wrap_rows(lidx);
+            if lidx == self.lineidx {
+                let top = (self.printlines + 2) as i32 + row as i32 - skip as i32;
+                self.wrapped = Some((lidx, top, starts.len()));
+            }
+            for r in skip..starts.len() {
+                if row == rows {
+                    return Ok(());
+                }
+                if row > 0 {
+                    self.term.queue(cursor::MoveToNextLine(1))?;
+                }
+                let end = starts.get(r + 1).copied().unwrap_or(self.lines[lidx].len());
+                self.drawgutter(if r == 0 { Some(lidx) } else { None })?;
+                self.drawline(lidx, starts[r], end, maxwidth)?;
+                row += 1;
+            }
+            skip = 0;
+        }
+        Ok(())
+    }
+
+    // Draw the rows of the cursor line again, or everything when the line
+    // now takes a different number of rows
+    pub(crate) fn redrawline_wrapped(&mut self) -> Result<()> {
+        let top = (self.scrollstart, self.wraptop);
+        self.setpos_wrapped()?;
+        if top != (self.scrollstart, self.wraptop) {
+            // Scrolled, and drawn already
+            return Ok(());
+        }
+        let starts = self.wrap_rows(self.lineidx);
+        let Some((_, first, _)) = self
+            .wrapped
+            .filter(|&(lidx, _, count)| lidx == self.lineidx && count == starts.len())
+        else {
+            return self.redraw();
+        };
+        let maxwidth = self.maxwidth();
+        for r in 0..starts.len() {
+            let row = first + r as i32;
+            if row < (self.printlines + 2) as i32 || row >= self.sizey as i32 {
+                continue;
+            }
+            let end = starts.get(r + 1).copied().unwrap_or(self.len());
+            self.term.queue(cursor::MoveTo(0, row as u16))?;
+            self.term
+                .queue(terminal::Clear(terminal::ClearType::CurrentLine))?;
+            self.drawgutter(if r == 0 { Some(self.lineidx) } else { None })?;
+            self.drawline(self.lineidx, starts[r], end, maxwidth)?;
+        }
+        self.draw_hint()?;
+        self.draw_completion()?;
+        self.term.queue(cursor::MoveTo(self.curx, self.cury))?;
+        Ok(())
+    }
+
+    // Set curx / cury for self.lidx, scrolling when the cursor leaves the screen
+    pub(crate) fn setpos_wrapped(&mut self) -> Result<()> {
+        self.lofs = 0;
+        self.loose_cursor = false;
+        self.lidx = self.grapheme_idx_at_idx(self.lidx);
+        let starts = self.wrap_rows(self.lineidx);
+        let r = row_of(&starts, self.lidx);
+        let line = &self.lines[self.lineidx];
+        self.curx = self.gutter()
+            + display_width(&line[starts[r]..self.lidx], self.tabstop as usize) as u16;
+
+        let top = (self.scrollstart, self.wraptop);
+        self.setrow_wrapped(r);
+        if top != (self.scrollstart, self.wraptop) {
+            self.redraw()?;
+        }
+        Ok(())
+    }
+
+    // Set scrollstart / wraptop / cury so row r of self.lineidx is visable
+    pub(crate) fn setrow_wrapped(&mut self, r: usize) {
+        let rows = self.sizey.saturating_sub(self.printlines + 2).max(1) as usize;
+        if self.scrollstart < self.lines.len() {
+            // The top line may have shrunk
+            self.wraptop = self.wraptop.min(self.wrap_rows(self.scrollstart).len() - 1);
+        }
+        if (self.lineidx, r) < (self.scrollstart, self.wraptop) {
+            // Above the screen, put it on top
+            self.scrollstart = self.lineidx;
+            self.wraptop = r;
+        }
+
+        // Count the rows from the top of the screen down to the cursor
+        let mut offset = 0;
+        let mut lidx = self.scrollstart;
+        while lidx < self.lineidx && offset < rows {
+            offset += self.wrap_rows(lidx).len();
+            lidx += 1;
+        }
+        offset = (offset + r).saturating_sub(self.wraptop);
+
+        if lidx < self.lineidx || offset >= rows {
+            // Below the screen, put it on the bottom row
+            let (mut lidx, mut r) = (self.lineidx, r);
+            let mut up = rows - 1;
+            while up > 0 {
+                if r >= up {
+                    r -= up;
+                    up = 0;
+                    break;
+                }
+                if lidx == 0 {
+                    up -= r;
+                    r = 0;
+                    break;
+                }
+                up -= r + 1;
+                lidx -= 1;
+                r = self.wrap_rows(lidx).len() - 1;
+            }
+            self.scrollstart = lidx;
+            self.wraptop = r;
+            offset = rows - 1 - up;
+        }
+        self.cury = offset as u16 + self.printlines + 2;
+    }
+
+    pub(crate) fn wrap_rows(&self, lineidx: usize) -> Vec<usize> {
+        wrap_line(&self.lines[lineidx], self.maxwidth(), self.tabstop as usize)
+    }
+}